@@ -0,0 +1,433 @@
+//! Keyframed parameters and rendering of numbered frame sequences.
+//!
+//! A [`Track`] holds keyframes for a single parameter, such as a camera
+//! position, a light intensity or a material color, and returns its value at
+//! any point in time. [`render_frames`] renders a range of frames to image
+//! files in the format and with the tone mapping of a [`FrameOutput`].
+
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use num::traits::Float;
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::exr::exr_from_canvas_with;
+use crate::hdr::hdr_from_canvas_with;
+use crate::png::png_from_canvas_with;
+use crate::ppm::ppm_from_canvas_with;
+use crate::quaternion::Quat;
+use crate::rgba::RgbaCanvas;
+use crate::srgb::Encoding;
+use crate::tonemap::ToneMapping;
+use crate::vectors::Vec3;
+
+/// Values that can be blended between keyframes.
+pub trait Animatable: Copy {
+    /// Blend from self to other, where `t = 0` gives self and `t = 1` other.
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Animatable for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Animatable for f64 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t as f64
+    }
+}
+
+impl<T: Float> Animatable for Vec3<T> {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * T::from(t).unwrap()
+    }
+}
+
+impl<T: Float> Animatable for Quat<T> {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self.slerp(other, T::from(t).unwrap())
+    }
+}
+
+impl Animatable for Color<f32> {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+/// How values change from one keyframe to the next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    /// Hold the value until the next keyframe.
+    Step,
+    /// Blend at constant speed.
+    Linear,
+    /// Blend along a cubic Bezier easing curve from (0, 0) to (1, 1).
+    ///
+    /// The two control points are given as `(x1, y1, x2, y2)`, like CSS
+    /// timing functions. The x-coordinates must lie in [0, 1].
+    Bezier(f32, f32, f32, f32),
+}
+
+impl Interpolation {
+    /// Ease-in-out curve.
+    pub const EASE: Interpolation = Interpolation::Bezier(0.42, 0.0, 0.58, 1.0);
+
+    /// Map linear progress in [0, 1] to blend factor.
+    pub fn ease(&self, t: f32) -> f32 {
+        match *self {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => t,
+            Interpolation::Bezier(x1, y1, x2, y2) => {
+                // Solve x(s) = t for the curve parameter by bisection, then
+                // evaluate y(s). x(s) is monotonic for x1, x2 in [0, 1].
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let middle = 0.5 * (low + high);
+                    if cubic_bezier(x1, x2, middle) < t {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                cubic_bezier(y1, y2, 0.5 * (low + high))
+            }
+        }
+    }
+}
+
+/// One-dimensional cubic Bezier with end points 0 and 1.
+fn cubic_bezier(p1: f32, p2: f32, s: f32) -> f32 {
+    let inv = 1.0 - s;
+    3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+}
+
+/// Value of a parameter at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe<V> {
+    /// Time in seconds
+    pub time: f32,
+    /// Value at this time
+    pub value: V,
+    /// Interpolation towards the next keyframe
+    pub interpolation: Interpolation,
+}
+
+/// Keyframes of a single animated parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<V> {
+    keyframes: Vec<Keyframe<V>>,
+}
+
+impl<V: Animatable> Track<V> {
+    /// Construct a new track holding a constant value.
+    pub fn new(value: V) -> Self {
+        Self {
+            keyframes: vec![Keyframe {
+                time: 0.0,
+                value,
+                interpolation: Interpolation::Linear,
+            }],
+        }
+    }
+
+    /// Add keyframe, replacing any keyframe at the same time.
+    pub fn with_key(mut self, time: f32, value: V, interpolation: Interpolation) -> Self {
+        self.keyframes.retain(|key| key.time != time);
+        let index = self.keyframes.partition_point(|key| key.time < time);
+        self.keyframes.insert(
+            index,
+            Keyframe {
+                time,
+                value,
+                interpolation,
+            },
+        );
+        self
+    }
+
+    /// Keyframes ordered by time.
+    pub fn keyframes(&self) -> &[Keyframe<V>] {
+        &self.keyframes
+    }
+
+    /// Return value at the given time.
+    ///
+    /// Before the first and after the last keyframe the value is held.
+    pub fn value_at(&self, time: f32) -> V {
+        let next = self.keyframes.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return self.keyframes[0].value;
+        }
+        let current = &self.keyframes[next - 1];
+        match self.keyframes.get(next) {
+            None => current.value,
+            Some(following) => {
+                let t = (time - current.time) / (following.time - current.time);
+                let t = current.interpolation.ease(t);
+                current.value.lerp(following.value, t)
+            }
+        }
+    }
+}
+
+/// Image format of rendered frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Plain PPM, 8-bit
+    Ppm,
+    /// PNG, 8-bit
+    Png,
+    /// Radiance HDR, floating point
+    Hdr,
+    /// OpenEXR, floating point
+    Exr,
+}
+
+impl FrameFormat {
+    /// File extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Ppm => "ppm",
+            FrameFormat::Png => "png",
+            FrameFormat::Hdr => "hdr",
+            FrameFormat::Exr => "exr",
+        }
+    }
+}
+
+/// Format and tone mapping to write frames with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameOutput {
+    /// Image format
+    pub format: FrameFormat,
+    /// Tone mapping applied before writing
+    pub tone_mapping: ToneMapping,
+}
+
+impl Default for FrameOutput {
+    /// PPM files without tone mapping.
+    fn default() -> Self {
+        Self::new(FrameFormat::Ppm)
+    }
+}
+
+impl FrameOutput {
+    /// Construct a new output in the given format without tone mapping.
+    pub fn new(format: FrameFormat) -> Self {
+        Self {
+            format,
+            tone_mapping: ToneMapping::default(),
+        }
+    }
+
+    /// Return output with the given tone mapping.
+    pub fn with_tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Write a canvas to bytes in the output format.
+    ///
+    /// 8-bit formats are encoded as their file extension expects.
+    pub fn encode(&self, canvas: &Canvas) -> Vec<u8> {
+        let extension = self.format.extension();
+        let encoding = Encoding::for_extension(extension);
+        match self.format {
+            FrameFormat::Ppm => {
+                ppm_from_canvas_with(canvas, &self.tone_mapping, encoding).into_bytes()
+            }
+            FrameFormat::Png => png_from_canvas_with(
+                &RgbaCanvas::from_canvas(canvas),
+                &self.tone_mapping,
+                encoding,
+            ),
+            FrameFormat::Hdr => hdr_from_canvas_with(canvas, &self.tone_mapping),
+            FrameFormat::Exr => {
+                exr_from_canvas_with(&RgbaCanvas::from_canvas(canvas), &self.tone_mapping)
+            }
+        }
+    }
+}
+
+/// Path of the file for a frame, numbered with four digits.
+pub fn frame_path(directory: &Path, frame: u32, format: FrameFormat) -> PathBuf {
+    directory.join(format!("frame_{:04}.{}", frame, format.extension()))
+}
+
+/// Render a range of frames to `frame_0001.ppm` style files in a directory.
+///
+/// The render function is called with the frame number and its time at the
+/// given frames per second, and frames are written as set by `output`. When
+/// `skip_existing` is set, frames whose file already exists are not rendered
+/// again, so that an interrupted job can be resumed. Returns the paths of the
+/// frames that were rendered.
+pub fn render_frames<F>(
+    frames: RangeInclusive<u32>,
+    fps: f32,
+    directory: &Path,
+    output: &FrameOutput,
+    skip_existing: bool,
+    mut render: F,
+) -> io::Result<Vec<PathBuf>>
+where
+    F: FnMut(u32, f32) -> Canvas,
+{
+    fs::create_dir_all(directory)?;
+    let mut rendered = vec![];
+    for frame in frames {
+        let path = frame_path(directory, frame, output.format);
+        if skip_existing && path.exists() {
+            continue;
+        }
+        let canvas = render(frame, frame as f32 / fps);
+        // Write to a temporary file first, so that a frame interrupted while
+        // writing is not mistaken for a finished one.
+        let partial = path.with_extension(format!("{}.partial", output.format.extension()));
+        fs::write(&partial, output.encode(&canvas))?;
+        fs::rename(&partial, &path)?;
+        rendered.push(path);
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod test_animation {
+    use std::fs;
+
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::hdr::canvas_from_hdr;
+    use crate::tonemap::Operator;
+    use crate::vectors::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn linear_track_interpolates_and_holds() {
+        let track = Track::new(0.0_f32)
+            .with_key(2.0, 10.0, Interpolation::Linear)
+            .with_key(1.0, 4.0, Interpolation::Linear);
+        assert_relative_eq!(track.value_at(-1.0), 0.0);
+        assert_relative_eq!(track.value_at(0.5), 2.0);
+        assert_relative_eq!(track.value_at(1.0), 4.0);
+        assert_relative_eq!(track.value_at(1.5), 7.0);
+        assert_relative_eq!(track.value_at(3.0), 10.0);
+    }
+
+    #[test]
+    fn step_track_holds_until_next_key() {
+        let track = Track::new(Color::new(1.0, 0.0, 0.0))
+            .with_key(0.0, Color::new(1.0, 0.0, 0.0), Interpolation::Step)
+            .with_key(1.0, Color::new(0.0, 1.0, 0.0), Interpolation::Step);
+        assert!(track.keyframes().len() == 2);
+        assert_relative_eq!(track.value_at(0.99), Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(track.value_at(1.0), Color::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn bezier_easing() {
+        let ease = Interpolation::EASE;
+        assert_relative_eq!(ease.ease(0.0), 0.0, epsilon = 1e-6);
+        assert_relative_eq!(ease.ease(0.5), 0.5, epsilon = 1e-6);
+        assert_relative_eq!(ease.ease(1.0), 1.0, epsilon = 1e-6);
+        // Slow start and end.
+        assert!(ease.ease(0.1) < 0.1);
+        assert!(ease.ease(0.9) > 0.9);
+        let linear = Interpolation::Bezier(0.25, 0.25, 0.75, 0.75);
+        assert_relative_eq!(linear.ease(0.3), 0.3, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn camera_position_track() {
+        let from = Vec3 {
+            x: 0.0,
+            y: 1.5,
+            z: -5.0,
+        };
+        let to = Vec3 {
+            x: 5.0,
+            y: 1.5,
+            z: 0.0,
+        };
+        let track = Track::new(from).with_key(4.0, to, Interpolation::Linear);
+        let expected = Vec3 {
+            x: 2.5,
+            y: 1.5,
+            z: -2.5,
+        };
+        assert_relative_eq!(track.value_at(2.0), expected);
+    }
+
+    #[test]
+    fn camera_orbit_track() {
+        let up = Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let end = Quat::from_axis_angle(up, 3.0);
+        let track = Track::new(Quat::identity()).with_key(2.0, end, Interpolation::Linear);
+        let (axis, angle) = track.value_at(0.5).to_axis_angle();
+        assert_relative_eq!(axis, up, epsilon = 1e-6);
+        assert_relative_eq!(angle, 0.75, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn render_frames_skips_existing() {
+        let directory =
+            std::env::temp_dir().join(format!("rust-raytracer-frames-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let intensity = Track::new(0.0_f32).with_key(1.0, 1.0, Interpolation::Linear);
+        let render = |_frame: u32, time: f32| {
+            let mut canvas = Canvas::new(1, 1);
+            let value = intensity.value_at(time);
+            canvas.fill(Color::new(value, value, value));
+            canvas
+        };
+
+        let output = FrameOutput::default();
+        let path = |frame| frame_path(&directory, frame, FrameFormat::Ppm);
+        let rendered = render_frames(1..=2, 2.0, &directory, &output, true, render).unwrap();
+        assert!(rendered == vec![path(1), path(2)]);
+        assert!(path(1).ends_with("frame_0001.ppm"));
+        let frame = fs::read_to_string(path(1)).unwrap();
+        assert!(frame.contains("188 188 188"));
+
+        let rendered = render_frames(1..=3, 2.0, &directory, &output, true, render).unwrap();
+        assert!(rendered == vec![path(3)]);
+        let rendered = render_frames(1..=3, 2.0, &directory, &output, false, render).unwrap();
+        assert!(rendered.len() == 3);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn render_frames_with_output_format() {
+        let directory =
+            std::env::temp_dir().join(format!("rust-raytracer-output-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let render = |_frame: u32, _time: f32| {
+            let mut canvas = Canvas::new(1, 1);
+            canvas.fill(Color::new(1.0, 1.0, 1.0));
+            canvas
+        };
+
+        let tone_mapping = ToneMapping::new(0.0, Operator::Reinhard);
+        let output = FrameOutput::new(FrameFormat::Hdr).with_tone_mapping(tone_mapping);
+        let rendered = render_frames(1..=1, 24.0, &directory, &output, false, render).unwrap();
+        assert!(rendered[0].ends_with("frame_0001.hdr"));
+        let frame = canvas_from_hdr(&fs::read(&rendered[0]).unwrap()).unwrap();
+        assert_relative_eq!(frame[(0, 0)], Color::new(0.5, 0.5, 0.5));
+
+        let output = FrameOutput::new(FrameFormat::Png);
+        let rendered = render_frames(1..=1, 24.0, &directory, &output, false, render).unwrap();
+        assert!(rendered[0].ends_with("frame_0001.png"));
+        assert!(fs::read(&rendered[0]).unwrap().starts_with(b"\x89PNG"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+//! Arbitrary output variables, extra per pixel buffers written during a render.
+//!
+//! Besides the beauty image, a render can record geometric data such as
+//! depth and normals, and split lighting into passes. [`RenderPasses`] holds
+//! the requested buffers, which can be exported as separate images or as
+//! layers of a single EXR file for compositing and denoising.
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::exr::{exr_from_channels, ExrChannel};
+
+/// Kind of output variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    /// Distance from the camera along the primary ray.
+    Depth,
+    /// World space position of the first hit.
+    Position,
+    /// Shading normal of the first hit.
+    Normal,
+    /// Surface color of the first hit, without lighting.
+    Albedo,
+    /// Identifier of the object hit first, 0 where nothing was hit.
+    ObjectId,
+    /// Identifier of the material hit first, 0 where nothing was hit.
+    MaterialId,
+    /// Texture coordinates of the first hit.
+    Uv,
+    /// Light reaching the camera after a single diffuse bounce.
+    Direct,
+    /// Light reaching the camera after more than one bounce.
+    Indirect,
+    /// Light reaching the camera through specular reflection.
+    Specular,
+}
+
+impl Aov {
+    /// All output variables.
+    pub const ALL: [Aov; 10] = [
+        Aov::Depth,
+        Aov::Position,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Uv,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::Specular,
+    ];
+
+    /// Return name used for files and layers.
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "objectId",
+            Aov::MaterialId => "materialId",
+            Aov::Uv => "uv",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Specular => "specular",
+        }
+    }
+
+    /// Return names of the channels, following common EXR conventions.
+    pub fn channel_names(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Position | Aov::Normal => &["X", "Y", "Z"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Uv => &["U", "V"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect | Aov::Specular => &["R", "G", "B"],
+        }
+    }
+
+    /// Return number of values per pixel.
+    pub fn channels(self) -> usize {
+        self.channel_names().len()
+    }
+}
+
+/// Float buffer holding one output variable for every pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AovBuffer {
+    /// Output variable stored
+    pub aov: Aov,
+    /// Height
+    pub height: usize,
+    /// Width
+    pub width: usize,
+    /// Values, with all channels of a pixel next to each other
+    pub data: Vec<f32>,
+}
+
+impl AovBuffer {
+    /// Construct a new buffer filled with zeros.
+    pub fn new(aov: Aov, height: usize, width: usize) -> Self {
+        Self {
+            aov,
+            height,
+            width,
+            data: vec![0.0; height * width * aov.channels()],
+        }
+    }
+
+    /// Get values at given row and column if index is in bounds.
+    pub fn get(&self, row: usize, column: usize) -> Option<&[f32]> {
+        let channels = self.aov.channels();
+        self.get_index(row, column)
+            .map(|index| &self.data[index * channels..(index + 1) * channels])
+    }
+
+    /// Get mutable values at given row and column if index is in bounds.
+    pub fn get_mut(&mut self, row: usize, column: usize) -> Option<&mut [f32]> {
+        let channels = self.aov.channels();
+        self.get_index(row, column)
+            .map(move |index| &mut self.data[index * channels..(index + 1) * channels])
+    }
+
+    fn get_index(&self, row: usize, column: usize) -> Option<usize> {
+        if row < self.height && column < self.width {
+            Some(row * self.width + column)
+        } else {
+            None
+        }
+    }
+
+    /// Return values of a single channel for all pixels.
+    pub fn channel(&self, channel: usize) -> Vec<f32> {
+        self.data
+            .iter()
+            .skip(channel)
+            .step_by(self.aov.channels())
+            .copied()
+            .collect()
+    }
+
+    /// Return buffer as canvas, for export as a separate image.
+    ///
+    /// Single channels become gray, and texture coordinates go to red and green.
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.height, self.width);
+        let channels = self.aov.channels();
+        for (pixel, values) in canvas.pixels.iter_mut().zip(self.data.chunks(channels)) {
+            *pixel = match *values {
+                [value] => Color::new(value, value, value),
+                [u, v] => Color::new(u, v, 0.0),
+                [r, g, b] => Color::new(r, g, b),
+                _ => unreachable!(),
+            };
+        }
+        canvas
+    }
+
+    /// Return channels for an EXR layer named after the output variable.
+    pub fn exr_channels(&self) -> Vec<ExrChannel> {
+        self.aov
+            .channel_names()
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let name = format!("{}.{}", self.aov.name(), name);
+                ExrChannel::new(&name, self.channel(index))
+            })
+            .collect()
+    }
+}
+
+/// Beauty image together with the output variables requested for a render.
+pub struct RenderPasses {
+    /// Final rendered image
+    pub beauty: Canvas,
+    /// Requested output variables
+    pub aovs: Vec<AovBuffer>,
+}
+
+impl RenderPasses {
+    /// Construct empty passes of the given size.
+    pub fn new(height: usize, width: usize, aovs: &[Aov]) -> Self {
+        Self {
+            beauty: Canvas::new(height, width),
+            aovs: aovs
+                .iter()
+                .map(|&aov| AovBuffer::new(aov, height, width))
+                .collect(),
+        }
+    }
+
+    /// Return buffer of an output variable, if it was requested.
+    pub fn aov(&self, aov: Aov) -> Option<&AovBuffer> {
+        self.aovs.iter().find(|buffer| buffer.aov == aov)
+    }
+
+    /// Return mutable buffer of an output variable, if it was requested.
+    pub fn aov_mut(&mut self, aov: Aov) -> Option<&mut AovBuffer> {
+        self.aovs.iter_mut().find(|buffer| buffer.aov == aov)
+    }
+
+    /// Store values of an output variable for a pixel.
+    ///
+    /// Does nothing if the variable was not requested, so a renderer can
+    /// record everything it knows without checking first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of values does not match the variable.
+    pub fn set(&mut self, row: usize, column: usize, aov: Aov, values: &[f32]) {
+        if let Some(target) = self
+            .aov_mut(aov)
+            .and_then(|buffer| buffer.get_mut(row, column))
+        {
+            target.copy_from_slice(values);
+        }
+    }
+
+    /// Return EXR bytes with the beauty image and every output variable as layer.
+    pub fn to_exr(&self) -> Vec<u8> {
+        let beauty = &self.beauty;
+        let mut channels = vec![
+            ExrChannel::new("R", beauty.pixels.iter().map(|pixel| pixel.r).collect()),
+            ExrChannel::new("G", beauty.pixels.iter().map(|pixel| pixel.g).collect()),
+            ExrChannel::new("B", beauty.pixels.iter().map(|pixel| pixel.b).collect()),
+        ];
+        for buffer in self.aovs.iter() {
+            channels.extend(buffer.exr_channels());
+        }
+        exr_from_channels(beauty.height, beauty.width, channels)
+    }
+}
+
+#[cfg(test)]
+mod test_aov {
+    use crate::color::Color;
+
+    use super::*;
+
+    #[test]
+    fn channels_per_aov() {
+        let total: usize = Aov::ALL.iter().map(|aov| aov.channels()).sum();
+        assert_eq!(total, 1 + 3 + 3 + 3 + 1 + 1 + 2 + 3 + 3 + 3);
+        assert_eq!(Aov::Uv.channel_names(), &["U", "V"]);
+    }
+
+    #[test]
+    fn only_requested_aovs_are_stored() {
+        let mut passes = RenderPasses::new(2, 3, &[Aov::Depth, Aov::Normal]);
+        passes.set(1, 2, Aov::Depth, &[4.5]);
+        passes.set(1, 2, Aov::Normal, &[0.0, 1.0, 0.0]);
+        passes.set(1, 2, Aov::Albedo, &[1.0, 1.0, 1.0]);
+        assert!(passes.aov(Aov::Albedo).is_none());
+        assert!(passes.aov(Aov::Depth).unwrap().get(1, 2) == Some(&[4.5][..]));
+        let normal = passes.aov(Aov::Normal).unwrap();
+        assert!(normal.get(1, 2) == Some(&[0.0, 1.0, 0.0][..]));
+        assert!(normal.get(2, 0).is_none());
+        assert!(normal.channel(1) == vec![0.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn export_as_canvas() {
+        let mut buffer = AovBuffer::new(Aov::Uv, 1, 2);
+        buffer.get_mut(0, 1).unwrap().copy_from_slice(&[0.25, 0.75]);
+        let canvas = buffer.to_canvas();
+        assert_relative_eq!(canvas[(0, 1)], Color::new(0.25, 0.75, 0.0));
+
+        let mut buffer = AovBuffer::new(Aov::Depth, 1, 1);
+        buffer.data[0] = 2.0;
+        assert_relative_eq!(buffer.to_canvas()[(0, 0)], Color::new(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn export_as_exr_layers() {
+        let passes = RenderPasses::new(1, 1, &[Aov::Uv, Aov::ObjectId]);
+        let names: Vec<String> = passes
+            .aov(Aov::Uv)
+            .unwrap()
+            .exr_channels()
+            .into_iter()
+            .map(|channel| channel.name)
+            .collect();
+        assert!(names == vec!["uv.U", "uv.V"]);
+        let exr = passes.to_exr();
+        let header = String::from_utf8_lossy(&exr);
+        assert!(header.contains("objectId.id\0"));
+        // R, G, B and three layer channels of a single float each.
+        assert!(exr.ends_with(&[0; 6 * 4]));
+    }
+}
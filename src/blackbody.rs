@@ -0,0 +1,128 @@
+//! Blackbody radiation and photometric light units.
+//!
+//! Lights are easier to specify the way lamps are sold, by color temperature
+//! in Kelvin and luminous flux in lumens or electrical power in watts.
+//! [`BlackbodyLight`] converts such a description to the color intensity
+//! used when shading.
+
+use std::f32::consts::PI;
+
+use crate::cie::{spectrum_to_xyz, LAMBDA_MAX, LAMBDA_MIN, Y_INTEGRAL};
+use crate::color::Color;
+
+/// Luminous efficacy of monochromatic light at 555 nm, in lumens per watt.
+pub const MAX_LUMINOUS_EFFICACY: f32 = 683.0;
+
+const PLANCK: f64 = 6.626_070_15e-34;
+const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+const BOLTZMANN: f64 = 1.380_649e-23;
+
+/// Spectral radiance of a blackbody at a wavelength in nanometers.
+///
+/// Given in W / (sr m² nm).
+pub fn planck(lambda: f32, kelvin: f32) -> f32 {
+    let lambda = lambda as f64 * 1e-9;
+    let kelvin = kelvin as f64;
+    let numerator = 2.0 * PLANCK * SPEED_OF_LIGHT * SPEED_OF_LIGHT / lambda.powi(5);
+    let exponent = PLANCK * SPEED_OF_LIGHT / (lambda * BOLTZMANN * kelvin);
+    (numerator / exponent.exp_m1() * 1e-9) as f32
+}
+
+/// Lumens emitted per watt of radiated power by a blackbody.
+pub fn luminous_efficacy(kelvin: f32) -> f32 {
+    let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+    let luminance = spectrum_to_xyz(|lambda| planck(lambda, kelvin), steps).y * Y_INTEGRAL;
+    // Stefan-Boltzmann law gives the radiance over all wavelengths.
+    let sigma = 5.670_374e-8;
+    let total = sigma * (kelvin as f64).powi(4) / std::f64::consts::PI;
+    MAX_LUMINOUS_EFFICACY * (luminance as f64 / total) as f32
+}
+
+/// Power of a light source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightPower {
+    /// Luminous flux in lumens.
+    Lumens(f32),
+    /// Radiated power in watts.
+    ///
+    /// This is the power leaving the light as radiation, which for a real
+    /// lamp is only part of the electrical power it draws.
+    Watts(f32),
+}
+
+/// Light source described by color temperature and power.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlackbodyLight {
+    /// Color temperature in Kelvin
+    pub temperature: f32,
+    /// Emitted power
+    pub power: LightPower,
+}
+
+impl BlackbodyLight {
+    /// Convenience function for creating a new light.
+    pub fn new(temperature: f32, power: LightPower) -> Self {
+        Self { temperature, power }
+    }
+
+    /// Return luminous flux in lumens.
+    pub fn lumens(&self) -> f32 {
+        match self.power {
+            LightPower::Lumens(lumens) => lumens,
+            LightPower::Watts(watts) => watts * luminous_efficacy(self.temperature),
+        }
+    }
+
+    /// Return radiated power in watts.
+    pub fn watts(&self) -> f32 {
+        match self.power {
+            LightPower::Lumens(lumens) => lumens / luminous_efficacy(self.temperature),
+            LightPower::Watts(watts) => watts,
+        }
+    }
+
+    /// Return intensity of a point light emitting equally in all directions.
+    ///
+    /// The luminance of the returned color is the luminous intensity in
+    /// candela, which is the flux divided by the full sphere of 4π steradians.
+    pub fn intensity(&self) -> Color<f32> {
+        Color::from_temperature(self.temperature) * (self.lumens() / (4.0 * PI))
+    }
+}
+
+#[cfg(test)]
+mod test_blackbody {
+    use super::*;
+
+    #[test]
+    fn planck_peaks_at_wien_wavelength() {
+        // Wien's displacement law: peak at 2897772 nm K / T.
+        let kelvin = 5000.0;
+        let peak = 2_897_772.0 / kelvin;
+        assert!(planck(peak, kelvin) > planck(peak - 10.0, kelvin));
+        assert!(planck(peak, kelvin) > planck(peak + 10.0, kelvin));
+        assert_relative_eq!(planck(500.0, 5000.0), 12_107.0, max_relative = 1e-3);
+    }
+
+    #[test]
+    fn efficacy_of_sunlight_and_incandescent() {
+        // The sun as a 5800 K blackbody gives about 93 lm/W.
+        assert_relative_eq!(luminous_efficacy(5800.0), 93.0, max_relative = 0.05);
+        // A 2800 K filament radiates about 15 lm/W.
+        assert_relative_eq!(luminous_efficacy(2800.0), 15.0, max_relative = 0.1);
+    }
+
+    #[test]
+    fn convert_between_lumens_and_watts() {
+        let light = BlackbodyLight::new(2700.0, LightPower::Lumens(800.0));
+        let watts = light.watts();
+        let same = BlackbodyLight::new(2700.0, LightPower::Watts(watts));
+        assert_relative_eq!(same.lumens(), 800.0, max_relative = 1e-5);
+    }
+
+    #[test]
+    fn point_light_intensity_in_candela() {
+        let light = BlackbodyLight::new(4000.0, LightPower::Lumens(4.0 * PI * 100.0));
+        assert_relative_eq!(light.intensity().luminance(), 100.0, max_relative = 1e-4);
+    }
+}
@@ -0,0 +1,229 @@
+//! Perturbation of shading normals by normal maps, bump maps and noise.
+//!
+//! All functions return a new shading normal and leave the geometric normal
+//! alone. The geometric normal should still be used to offset points when
+//! spawning secondary rays, otherwise perturbed normals cause self-shadowing
+//! artifacts.
+
+use num::traits::Float;
+
+use crate::color::Color;
+use crate::noise::perlin;
+use crate::texture::ImageTexture;
+use crate::vectors::Vec3;
+
+/// Return the tangent of a triangle along increasing u, and its handedness.
+///
+/// Takes the three vertices and their uv-coordinates. Per-vertex tangents are
+/// found by summing the tangents of all triangles sharing the vertex and
+/// normalizing the result. Returns `None` for degenerate uv-coordinates.
+///
+/// The handedness is 1, or -1 where the uv-coordinates are mirrored, and is
+/// what is usually stored as the w component of vertex tangents. It is
+/// relative to the normal of the counter-clockwise winding of the vertices.
+pub fn triangle_tangent<T: Float>(
+    positions: [Vec3<T>; 3],
+    uvs: [(T, T); 3],
+) -> Option<(Vec3<T>, T)> {
+    let edge1 = positions[1] - positions[0];
+    let edge2 = positions[2] - positions[0];
+    let (du1, dv1) = (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1);
+    let (du2, dv2) = (uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1);
+    let determinant = du1 * dv2 - du2 * dv1;
+    if determinant.abs() <= T::epsilon() {
+        return None;
+    }
+    let tangent = (edge1 * dv2 - edge2 * dv1) / determinant;
+    // The uv-coordinates run clockwise on the surface when mirrored.
+    let handedness = determinant.signum();
+    Some((tangent.normalize(), handedness))
+}
+
+/// Perturb a normal by a tangent space normal map sample.
+///
+/// The sample stores the x, y and z components in its r, g and b channels,
+/// mapped from [-1, 1] to [0, 1]. The tangent does not have to be exactly
+/// perpendicular to the normal. The handedness from [`triangle_tangent`]
+/// flips the bitangent, so that mirrored uv-coordinates read the green
+/// channel the right way around.
+pub fn normal_from_map<T: Float>(
+    normal: &Vec3<T>,
+    tangent: &Vec3<T>,
+    handedness: T,
+    sample: Color<f32>,
+) -> Vec3<T> {
+    let decode = |value: f32| T::from(value * 2.0 - 1.0).unwrap();
+    let (tangent, bitangent) = tangent_frame(normal, tangent, handedness);
+    (tangent * decode(sample.r) + bitangent * decode(sample.g) + *normal * decode(sample.b))
+        .normalize()
+}
+
+/// Perturb a normal by the slope of a height map at uv-coordinates.
+///
+/// Heights are the average of the color channels of the texture. The slope
+/// is estimated by central differences of one texel, and `strength` scales
+/// the resulting tilt. The handedness is as for [`normal_from_map`].
+pub fn normal_from_bump_map<T: Float>(
+    normal: &Vec3<T>,
+    tangent: &Vec3<T>,
+    handedness: T,
+    texture: &ImageTexture,
+    u: f32,
+    v: f32,
+    strength: T,
+) -> Vec3<T> {
+    let height = |u: f32, v: f32| {
+        let color = texture.color_at(u, v);
+        (color.r + color.g + color.b) / 3.0
+    };
+    let du = 1.0 / texture.canvas.width as f32;
+    let dv = 1.0 / texture.canvas.height as f32;
+    let slope_u = (height(u + du, v) - height(u - du, v)) * 0.5;
+    let slope_v = (height(u, v + dv) - height(u, v - dv)) * 0.5;
+
+    let (tangent, bitangent) = tangent_frame(normal, tangent, handedness);
+    let tilt_u = T::from(slope_u).unwrap() * strength;
+    let tilt_v = T::from(slope_v).unwrap() * strength;
+    (*normal - tangent * tilt_u - bitangent * tilt_v).normalize()
+}
+
+/// Perturb a normal with Perlin noise sampled around a point.
+///
+/// `scale` sets the frequency of the noise and `strength` how far the normal
+/// is tilted. This mirrors the "perturbed normals" option from the book.
+pub fn normal_from_noise<T: Float>(
+    normal: &Vec3<T>,
+    point: &Vec3<T>,
+    scale: T,
+    strength: T,
+) -> Vec3<T> {
+    let p = *point * scale;
+    let (x, y, z) = (
+        p.x.to_f64().unwrap(),
+        p.y.to_f64().unwrap(),
+        p.z.to_f64().unwrap(),
+    );
+    // Offset the lookups so that each component gets uncorrelated noise.
+    let offset = Vec3 {
+        x: T::from(perlin(x, y, z)).unwrap(),
+        y: T::from(perlin(x + 31.416, y + 27.183, z + 14.142)).unwrap(),
+        z: T::from(perlin(x - 17.321, y - 22.361, z + 26.458)).unwrap(),
+    };
+    (*normal + offset * strength).normalize()
+}
+
+/// Build tangent and bitangent perpendicular to the normal.
+fn tangent_frame<T: Float>(
+    normal: &Vec3<T>,
+    tangent: &Vec3<T>,
+    handedness: T,
+) -> (Vec3<T>, Vec3<T>) {
+    // Gram-Schmidt: remove the part of the tangent along the normal.
+    let tangent = (*tangent - *normal * normal.dot(*tangent)).normalize();
+    let bitangent = normal.cross(&tangent) * handedness.signum();
+    (tangent, bitangent)
+}
+
+#[cfg(test)]
+mod test_bump {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::texture::{ImageTexture, WrapMode};
+    use crate::vectors::Vec3;
+
+    use super::*;
+
+    fn vector(x: f64, y: f64, z: f64) -> Vec3<f64> {
+        Vec3 { x, y, z }
+    }
+
+    #[test]
+    fn tangent_of_triangle_follows_u() {
+        let positions = [
+            vector(0.0, 0.0, 0.0),
+            vector(2.0, 0.0, 0.0),
+            vector(0.0, 2.0, 0.0),
+        ];
+        let uvs = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
+        let (tangent, handedness) = triangle_tangent(positions, uvs).unwrap();
+        assert_relative_eq!(tangent, vector(1.0, 0.0, 0.0));
+        assert_relative_eq!(handedness, 1.0);
+
+        let degenerate = [(0.0, 0.0), (0.0, 0.0), (0.0, 0.0)];
+        assert!(triangle_tangent(positions, degenerate).is_none());
+    }
+
+    #[test]
+    fn flat_normal_map_keeps_normal() {
+        let normal = vector(0.0, 1.0, 0.0);
+        let tangent = vector(1.0, 0.0, 0.0);
+        let flat = Color::new(0.5, 0.5, 1.0);
+        assert_relative_eq!(normal_from_map(&normal, &tangent, 1.0, flat), normal);
+    }
+
+    #[test]
+    fn mirrored_uvs_keep_green_channel() {
+        let positions = [
+            vector(0.0, 0.0, 0.0),
+            vector(2.0, 0.0, 0.0),
+            vector(0.0, 2.0, 0.0),
+        ];
+        let normal = vector(0.0, 0.0, 1.0);
+        // Green tilts the normal towards increasing v, which is +y for both.
+        let sample = Color::new(0.5, 1.0, 0.5);
+        for uvs in [
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            [(1.0, 0.0), (0.0, 0.0), (1.0, 1.0)],
+        ]
+        .iter()
+        {
+            let (tangent, handedness) = triangle_tangent(positions, *uvs).unwrap();
+            let result = normal_from_map(&normal, &tangent, handedness, sample);
+            assert_relative_eq!(result, vector(0.0, 1.0, 0.0), epsilon = 1e-6);
+        }
+        let mirrored = [(1.0, 0.0), (0.0, 0.0), (1.0, 1.0)];
+        let (tangent, handedness) = triangle_tangent(positions, mirrored).unwrap();
+        assert_relative_eq!(tangent, vector(-1.0, 0.0, 0.0));
+        assert_relative_eq!(handedness, -1.0);
+    }
+
+    #[test]
+    fn normal_map_tilts_towards_tangent() {
+        let normal = vector(0.0, 1.0, 0.0);
+        // Not quite perpendicular, is corrected internally.
+        let tangent = vector(1.0, 0.2, 0.0);
+        let sample = Color::new(1.0, 0.5, 0.5);
+        let result = normal_from_map(&normal, &tangent, 1.0, sample);
+        assert_relative_eq!(result, vector(1.0, 0.0, 0.0), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn bump_map_tilts_away_from_slope() {
+        // Height increases along u.
+        let mut canvas = Canvas::new(1, 4);
+        for column in 0..4 {
+            let height = column as f32 / 4.0;
+            canvas[(0, column)] = Color::new(height, height, height);
+        }
+        let texture = ImageTexture::new(canvas).with_wrap(WrapMode::Clamp);
+        let normal = vector(0.0, 0.0, 1.0);
+        let tangent = vector(1.0, 0.0, 0.0);
+        let result = normal_from_bump_map(&normal, &tangent, 1.0, &texture, 0.5, 0.5, 4.0);
+        assert!(result.x < 0.0);
+        assert_relative_eq!(result.y, 0.0);
+        assert_relative_eq!(result.magnitude(), 1.0);
+
+        let flat = normal_from_bump_map(&normal, &tangent, 1.0, &texture, 0.5, 0.5, 0.0);
+        assert_relative_eq!(flat, normal);
+    }
+
+    #[test]
+    fn noise_perturbs_normal() {
+        let normal = vector(0.0, 1.0, 0.0);
+        let point = vector(0.3, 0.7, 1.9);
+        let result = normal_from_noise(&normal, &point, 1.0, 0.5);
+        assert_relative_eq!(result.magnitude(), 1.0);
+        assert!(result != normal);
+        assert_relative_eq!(normal_from_noise(&normal, &point, 1.0, 0.0), normal);
+    }
+}
@@ -2,12 +2,59 @@
 //!
 //!
 
+use std::f32::consts::PI;
 use std::ops::{Index, IndexMut};
 
 use crate::color::Color;
 
+/// Filter used when resizing a canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resample {
+    /// Use the closest source pixel.
+    Nearest,
+    /// Triangle filter, which is bilinear interpolation when enlarging.
+    Bilinear,
+    /// Windowed sinc with three lobes, sharper but can ring at hard edges.
+    Lanczos3,
+}
+
+impl Resample {
+    /// Return distance beyond which the kernel is zero.
+    fn radius(self) -> f32 {
+        match self {
+            Resample::Nearest => 0.5,
+            Resample::Bilinear => 1.0,
+            Resample::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Resample::Nearest => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Resample::Bilinear => (1.0 - x).max(0.0),
+            Resample::Lanczos3 => {
+                if x < 1e-6 {
+                    1.0
+                } else if x < 3.0 {
+                    let pi_x = PI * x;
+                    3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
 /// Represents a 2d Canvas
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Canvas {
     /// Height
     pub height: usize,
@@ -45,6 +92,130 @@ impl Canvas {
             .map(move |index| &mut self.pixels[index])
     }
 
+    /// Return the part of the canvas starting at row and column.
+    ///
+    /// The size is clipped to the canvas bounds.
+    pub fn crop(&self, row: usize, column: usize, height: usize, width: usize) -> Canvas {
+        let height = height.min(self.height.saturating_sub(row));
+        let width = width.min(self.width.saturating_sub(column));
+        let mut result = Canvas::new(height, width);
+        for r in 0..height {
+            let start = (row + r) * self.width + column;
+            result.pixels[r * width..(r + 1) * width]
+                .copy_from_slice(&self.pixels[start..start + width]);
+        }
+        result
+    }
+
+    /// Return the canvas scaled to a new size.
+    ///
+    /// Filters other than nearest are widened when shrinking, so that every
+    /// source pixel contributes. Lanczos can produce values slightly outside
+    /// the range of the source, these are kept. An empty canvas resizes to a
+    /// black one.
+    pub fn resize(&self, height: usize, width: usize, filter: Resample) -> Canvas {
+        if self.height == 0 || self.width == 0 {
+            return Canvas::new(height, width);
+        }
+        let horizontal = resample_weights(self.width, width, filter);
+        let vertical = resample_weights(self.height, height, filter);
+        // Filter rows first, then columns of the intermediate result.
+        let mut rows = Canvas::new(self.height, width);
+        for r in 0..self.height {
+            for (c, taps) in horizontal.iter().enumerate() {
+                rows[(r, c)] = taps
+                    .iter()
+                    .fold(Color::new(0.0, 0.0, 0.0), |sum, &(index, weight)| {
+                        sum + self[(r, index)] * weight
+                    });
+            }
+        }
+        let mut result = Canvas::new(height, width);
+        for (r, taps) in vertical.iter().enumerate() {
+            for c in 0..width {
+                result[(r, c)] = taps
+                    .iter()
+                    .fold(Color::new(0.0, 0.0, 0.0), |sum, &(index, weight)| {
+                        sum + rows[(index, c)] * weight
+                    });
+            }
+        }
+        result
+    }
+
+    /// Return the canvas mirrored left to right.
+    pub fn flip_horizontal(&self) -> Canvas {
+        self.remap(self.height, self.width, |r, c| (r, self.width - 1 - c))
+    }
+
+    /// Return the canvas mirrored top to bottom.
+    pub fn flip_vertical(&self) -> Canvas {
+        self.remap(self.height, self.width, |r, c| (self.height - 1 - r, c))
+    }
+
+    /// Return the canvas rotated a quarter turn clockwise.
+    pub fn rotate_90(&self) -> Canvas {
+        self.remap(self.width, self.height, |r, c| (self.height - 1 - c, r))
+    }
+
+    /// Return the canvas rotated half a turn.
+    pub fn rotate_180(&self) -> Canvas {
+        self.remap(self.height, self.width, |r, c| {
+            (self.height - 1 - r, self.width - 1 - c)
+        })
+    }
+
+    /// Return the canvas rotated a quarter turn counterclockwise.
+    pub fn rotate_270(&self) -> Canvas {
+        self.remap(self.width, self.height, |r, c| (c, self.width - 1 - r))
+    }
+
+    /// Build a canvas whose pixels are taken from source positions of self.
+    fn remap<F>(&self, height: usize, width: usize, source: F) -> Canvas
+    where
+        F: Fn(usize, usize) -> (usize, usize),
+    {
+        let mut result = Canvas::new(height, width);
+        for r in 0..height {
+            for c in 0..width {
+                result[(r, c)] = self[source(r, c)];
+            }
+        }
+        result
+    }
+
+    /// Copy another canvas onto this one with its top left corner at row and column.
+    ///
+    /// The offset may be negative, and parts outside this canvas are skipped.
+    pub fn blit(&mut self, source: &Canvas, row: isize, column: isize) {
+        for r in 0..source.height {
+            for c in 0..source.width {
+                let (target_row, target_column) = (row + r as isize, column + c as isize);
+                if target_row < 0 || target_column < 0 {
+                    continue;
+                }
+                if let Some(pixel) = self.get_mut(target_row as usize, target_column as usize) {
+                    *pixel = source[(r, c)];
+                }
+            }
+        }
+    }
+
+    /// Return the canvas surrounded by borders of the given color.
+    pub fn pad(
+        &self,
+        top: usize,
+        right: usize,
+        bottom: usize,
+        left: usize,
+        color: Color<f32>,
+    ) -> Canvas {
+        let mut result = Canvas::new(self.height + top + bottom, self.width + left + right);
+        result.fill(color);
+        result.blit(self, top as isize, left as isize);
+        result
+    }
+
     /// Get index for row & column
     fn get_index(&self, row: usize, column: usize) -> Option<usize> {
         if row < self.height && column < self.width {
@@ -55,6 +226,42 @@ impl Canvas {
     }
 }
 
+/// Return for every target position the source indices and normalized weights.
+fn resample_weights(source: usize, target: usize, filter: Resample) -> Vec<Vec<(usize, f32)>> {
+    let scale = target as f32 / source as f32;
+    // Stretch the kernel over more source pixels when shrinking. Nearest
+    // should still pick a single pixel.
+    let stretch = match filter {
+        Resample::Nearest => 1.0,
+        _ => (1.0 / scale).max(1.0),
+    };
+    let radius = filter.radius() * stretch;
+    (0..target)
+        .map(|t| {
+            let center = (t as f32 + 0.5) / scale - 0.5;
+            let first = (center - radius).floor() as isize;
+            let last = (center + radius).ceil() as isize;
+            let mut taps: Vec<(usize, f32)> = (first..=last)
+                .map(|s| {
+                    let weight = filter.weight((s as f32 - center) / stretch);
+                    (s.clamp(0, source as isize - 1) as usize, weight)
+                })
+                .filter(|&(_, weight)| weight != 0.0)
+                .collect();
+            if taps.is_empty() {
+                // Nearest can fall exactly between two pixels.
+                let nearest = (center.round() as isize).clamp(0, source as isize - 1);
+                taps.push((nearest as usize, 1.0));
+            }
+            let total: f32 = taps.iter().map(|&(_, weight)| weight).sum();
+            for tap in taps.iter_mut() {
+                tap.1 /= total;
+            }
+            taps
+        })
+        .collect()
+}
+
 impl Index<(usize, usize)> for Canvas {
     type Output = Color<f32>;
 
@@ -75,7 +282,23 @@ impl IndexMut<(usize, usize)> for Canvas {
 mod test_canvas {
     use crate::color::Color;
 
-    use super::Canvas;
+    use super::{Canvas, Resample};
+
+    /// Canvas where the red channel holds the row and green the column.
+    fn numbered(height: usize, width: usize) -> Canvas {
+        let mut canvas = Canvas::new(height, width);
+        for row in 0..height {
+            for column in 0..width {
+                canvas[(row, column)] = Color::new(row as f32, column as f32, 0.0);
+            }
+        }
+        canvas
+    }
+
+    fn position(canvas: &Canvas, row: usize, column: usize) -> (f32, f32) {
+        let pixel = canvas[(row, column)];
+        (pixel.r, pixel.g)
+    }
 
     #[test]
     fn create_canvas() {
@@ -95,4 +318,121 @@ mod test_canvas {
         canvas[(2, 3)] = Color::new(1.0, 0.0, 0.0);
         assert!(canvas[(2, 3)] == Color::new(1.0, 0.0, 0.0))
     }
+
+    #[test]
+    fn crop_canvas() {
+        let canvas = numbered(4, 5);
+        let cropped = canvas.crop(1, 2, 2, 2);
+        assert!(cropped.height == 2 && cropped.width == 2);
+        assert!(position(&cropped, 0, 0) == (1.0, 2.0));
+        assert!(position(&cropped, 1, 1) == (2.0, 3.0));
+        // Clipped to the canvas.
+        let clipped = canvas.crop(3, 3, 10, 10);
+        assert!(clipped.height == 1 && clipped.width == 2);
+        assert!(canvas.crop(9, 9, 2, 2).pixels.is_empty());
+    }
+
+    #[test]
+    fn flip_and_rotate_canvas() {
+        let canvas = numbered(2, 3);
+        assert!(position(&canvas.flip_horizontal(), 0, 0) == (0.0, 2.0));
+        assert!(position(&canvas.flip_vertical(), 0, 0) == (1.0, 0.0));
+
+        let rotated = canvas.rotate_90();
+        assert!(rotated.height == 3 && rotated.width == 2);
+        // The bottom left corner moves to the top left.
+        assert!(position(&rotated, 0, 0) == (1.0, 0.0));
+        assert!(position(&rotated, 0, 1) == (0.0, 0.0));
+        assert!(position(&rotated, 2, 1) == (0.0, 2.0));
+
+        let rotated = canvas.rotate_270();
+        assert!(position(&rotated, 0, 0) == (0.0, 2.0));
+        assert!(position(&rotated, 2, 1) == (1.0, 0.0));
+
+        assert!(canvas.rotate_180().pixels == canvas.flip_horizontal().flip_vertical().pixels);
+        assert!(canvas.rotate_90().rotate_270().pixels == canvas.pixels);
+    }
+
+    #[test]
+    fn blit_with_clipping() {
+        let mut canvas = Canvas::new(3, 3);
+        let mut stamp = Canvas::new(2, 2);
+        stamp.fill(Color::new(1.0, 1.0, 1.0));
+        canvas.blit(&stamp, -1, 2);
+        assert!(canvas[(0, 2)] == Color::new(1.0, 1.0, 1.0));
+        assert!(canvas[(1, 2)] == Color::new(0.0, 0.0, 0.0));
+        let lit = canvas.pixels.iter().filter(|pixel| pixel.r == 1.0).count();
+        assert!(lit == 1);
+    }
+
+    #[test]
+    fn pad_canvas() {
+        let canvas = numbered(1, 2);
+        let padded = canvas.pad(1, 2, 3, 4, Color::new(0.5, 0.5, 0.5));
+        assert!(padded.height == 5 && padded.width == 8);
+        assert!(padded[(0, 0)] == Color::new(0.5, 0.5, 0.5));
+        assert!(position(&padded, 1, 5) == (0.0, 1.0));
+        assert!(padded[(4, 7)] == Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn resize_nearest() {
+        let canvas = numbered(2, 2);
+        let enlarged = canvas.resize(4, 4, Resample::Nearest);
+        assert!(position(&enlarged, 1, 1) == (0.0, 0.0));
+        assert!(position(&enlarged, 2, 3) == (1.0, 1.0));
+        let shrunk = numbered(4, 4).resize(2, 2, Resample::Nearest);
+        assert!(shrunk.height == 2 && shrunk.width == 2);
+        assert!(position(&shrunk, 0, 0) == (1.0, 1.0));
+        assert!(position(&shrunk, 1, 1) == (3.0, 3.0));
+    }
+
+    #[test]
+    fn resize_bilinear() {
+        let mut canvas = Canvas::new(1, 2);
+        canvas[(0, 1)] = Color::new(1.0, 1.0, 1.0);
+        let enlarged = canvas.resize(1, 4, Resample::Bilinear);
+        let values: Vec<f32> = enlarged.pixels.iter().map(|pixel| pixel.r).collect();
+        assert_relative_eq!(values[..], [0.0, 0.25, 0.75, 1.0][..]);
+        // Shrinking averages, keeping the mean.
+        let shrunk = numbered(4, 4).resize(2, 2, Resample::Bilinear);
+        let mean: f32 = shrunk.pixels.iter().map(|pixel| pixel.g).sum::<f32>() / 4.0;
+        assert_relative_eq!(mean, 1.5, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn resize_empty_canvas() {
+        for filter in [Resample::Nearest, Resample::Bilinear, Resample::Lanczos3].iter() {
+            let resized = Canvas::new(0, 0).resize(2, 3, *filter);
+            assert!(resized.height == 2 && resized.width == 3);
+            assert!(resized.pixels == Canvas::new(2, 3).pixels);
+            assert!(Canvas::new(0, 4).resize(2, 2, *filter).pixels.len() == 4);
+            assert!(numbered(2, 2).resize(0, 0, *filter).pixels.is_empty());
+        }
+    }
+
+    #[test]
+    fn resize_lanczos() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.fill(Color::new(0.25, 0.5, 2.0));
+        for (height, width) in [(8, 8), (2, 2), (3, 7)].iter() {
+            let resized = canvas.resize(*height, *width, Resample::Lanczos3);
+            for pixel in resized.pixels.iter() {
+                assert_relative_eq!(*pixel, Color::new(0.25, 0.5, 2.0), epsilon = 1e-5);
+            }
+        }
+        // Same size leaves pixels untouched.
+        let canvas = numbered(3, 5);
+        let same = canvas.resize(3, 5, Resample::Lanczos3);
+        for (a, b) in same.pixels.iter().zip(canvas.pixels.iter()) {
+            assert_relative_eq!(*a, *b, epsilon = 1e-5);
+        }
+        // Ringing overshoots a hard edge, and is kept.
+        let mut edge = Canvas::new(1, 8);
+        for column in 4..8 {
+            edge[(0, column)] = Color::new(1.0, 1.0, 1.0);
+        }
+        let enlarged = edge.resize(1, 32, Resample::Lanczos3);
+        assert!(enlarged.pixels.iter().any(|pixel| pixel.r > 1.0));
+    }
 }
@@ -0,0 +1,96 @@
+//! CIE 1931 standard observer color matching functions.
+//!
+//! Uses the multi-lobe Gaussian fit by Wyman, Sloan and Shirley (2013),
+//! which is within a few percent of the tabulated functions and avoids
+//! carrying the tables around.
+
+use crate::colorspace::Xyz;
+
+/// Shortest wavelength in nanometers taken into account.
+pub const LAMBDA_MIN: f32 = 360.0;
+
+/// Longest wavelength in nanometers taken into account.
+pub const LAMBDA_MAX: f32 = 830.0;
+
+/// Integral of the fitted y-bar function over [LAMBDA_MIN, LAMBDA_MAX].
+///
+/// Spectra integrated against the matching functions are divided by this, so
+/// that a constant spectrum of 1.0 has luminance 1.0.
+pub const Y_INTEGRAL: f32 = 106.922_07;
+
+/// Piecewise Gaussian with different widths left and right of the mean.
+fn lobe(lambda: f32, mean: f32, sigma_left: f32, sigma_right: f32) -> f32 {
+    let sigma = if lambda < mean {
+        sigma_left
+    } else {
+        sigma_right
+    };
+    let t = (lambda - mean) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+/// x-bar color matching function at a wavelength in nanometers.
+pub fn x_bar(lambda: f32) -> f32 {
+    1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(lambda, 501.1, 20.4, 26.2)
+}
+
+/// y-bar color matching function at a wavelength in nanometers.
+pub fn y_bar(lambda: f32) -> f32 {
+    0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1)
+}
+
+/// z-bar color matching function at a wavelength in nanometers.
+pub fn z_bar(lambda: f32) -> f32 {
+    1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8)
+}
+
+/// Integrate a spectrum against the matching functions, using the midpoint rule.
+///
+/// The result is normalized with [`Y_INTEGRAL`].
+pub fn spectrum_to_xyz<F: Fn(f32) -> f32>(spectrum: F, steps: usize) -> Xyz {
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f32;
+    let mut xyz = Xyz {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    for i in 0..steps {
+        let lambda = LAMBDA_MIN + (i as f32 + 0.5) * step;
+        let value = spectrum(lambda);
+        xyz.x += value * x_bar(lambda);
+        xyz.y += value * y_bar(lambda);
+        xyz.z += value * z_bar(lambda);
+    }
+    let scale = step / Y_INTEGRAL;
+    Xyz {
+        x: xyz.x * scale,
+        y: xyz.y * scale,
+        z: xyz.z * scale,
+    }
+}
+
+#[cfg(test)]
+mod test_cie {
+    use super::*;
+
+    #[test]
+    fn matching_functions_near_tabulated_values() {
+        // CIE 1931 2° observer table.
+        assert_relative_eq!(x_bar(555.0), 0.5121, epsilon = 1e-2);
+        assert_relative_eq!(y_bar(555.0), 1.0, epsilon = 1e-2);
+        assert_relative_eq!(z_bar(555.0), 0.0057, epsilon = 1e-2);
+        assert_relative_eq!(x_bar(450.0), 0.3362, epsilon = 2e-2);
+        assert_relative_eq!(z_bar(450.0), 1.7721, epsilon = 5e-2);
+        assert_relative_eq!(y_bar(600.0), 0.631, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn constant_spectrum_is_equal_energy_white() {
+        let xyz = spectrum_to_xyz(|_| 1.0, 470);
+        assert_relative_eq!(xyz.y, 1.0, epsilon = 1e-4);
+        // Equal energy white has (nearly) equal X, Y and Z.
+        assert_relative_eq!(xyz.x, 1.0, epsilon = 2e-3);
+        assert_relative_eq!(xyz.z, 1.0, epsilon = 2e-3);
+    }
+}
@@ -8,6 +8,10 @@ use std::ops::{Add, Mul, Sub};
 
 use approx::{AbsDiffEq, RelativeEq, UlpsEq};
 
+use crate::blackbody::planck;
+use crate::cie::spectrum_to_xyz;
+use crate::colorspace::SRGB;
+
 /// Represents a color
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color<T> {
@@ -27,18 +31,48 @@ impl<T> Color<T> {
 
     /// Return the r, g and b values as array.
     pub fn as_array(self) -> [T; 3] {
-        return [self.r, self.g, self.b];
+        [self.r, self.g, self.b]
     }
 }
 
 impl Color<f32> {
     /// Return Color with rgb values as 8-bit integer.
     pub fn as_rgb8(self) -> Color<u8> {
-        return Color::<u8>::new(
+        Color::<u8>::new(
             clamp((self.r * 255.0).round() as u8, 0, 255),
             clamp((self.g * 255.0).round() as u8, 0, 255),
             clamp((self.b * 255.0).round() as u8, 0, 255),
-        );
+        )
+    }
+
+    /// Return whether all channels are neither NaN nor infinite.
+    pub fn is_finite(self) -> bool {
+        self.r.is_finite() && self.g.is_finite() && self.b.is_finite()
+    }
+
+    /// Return relative luminance, using Rec. 709 weights on linear rgb.
+    pub fn luminance(self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /// Return linear rgb color of a blackbody at the given temperature in Kelvin.
+    ///
+    /// Planck's law is integrated against the CIE matching functions, and the
+    /// result is scaled to luminance 1. Channels outside the sRGB gamut, such
+    /// as blue below about 1900 K, are clipped to zero. Temperatures too low
+    /// to emit visible light, including zero and below, give black.
+    pub fn from_temperature(kelvin: f32) -> Self {
+        if kelvin.is_nan() || kelvin <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let xyz = spectrum_to_xyz(|lambda| planck(lambda, kelvin), 470);
+        let rgb = SRGB.from_xyz(xyz);
+        let rgb = Color::new(rgb.r.max(0.0), rgb.g.max(0.0), rgb.b.max(0.0));
+        let luminance = rgb.luminance();
+        if luminance <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        rgb * (1.0 / luminance)
     }
 }
 
@@ -47,7 +81,7 @@ impl<T> IntoIterator for Color<T> {
     type IntoIter = std::array::IntoIter<T, 3>;
 
     fn into_iter(self) -> Self::IntoIter {
-        std::array::IntoIter::new([self.r, self.b, self.g])
+        IntoIterator::into_iter([self.r, self.b, self.g])
     }
 }
 
@@ -189,4 +223,33 @@ mod test_color {
         let expected_result: Color<f32> = Color::new(0.9, 0.2, 0.04);
         assert_relative_eq!(result, expected_result)
     }
+
+    #[test]
+    fn luminance_of_color() {
+        assert_relative_eq!(Color::new(1.0, 1.0, 1.0).luminance(), 1.0);
+        assert_relative_eq!(Color::new(0.0, 1.0, 0.0).luminance(), 0.7152);
+    }
+
+    #[test]
+    fn color_from_temperature() {
+        for kelvin in [1000.0, 2700.0, 6500.0, 12000.0].iter() {
+            let color = Color::from_temperature(*kelvin);
+            assert_relative_eq!(color.luminance(), 1.0, epsilon = 1e-5);
+        }
+        let warm = Color::from_temperature(2700.0);
+        assert!(warm.r > warm.g && warm.g > warm.b);
+        let cool = Color::from_temperature(12000.0);
+        assert!(cool.b > cool.g && cool.g > cool.r);
+        // A blackbody near 6500 K is close to the D65 white of sRGB.
+        let daylight = Color::from_temperature(6500.0);
+        assert_relative_eq!(daylight, Color::new(1.0, 1.0, 1.0), epsilon = 0.1);
+    }
+
+    #[test]
+    fn color_from_cold_temperature_is_black() {
+        let black = Color::new(0.0, 0.0, 0.0);
+        for kelvin in [0.0, -300.0, 1.0, f32::NAN].iter() {
+            assert_relative_eq!(Color::from_temperature(*kelvin), black);
+        }
+    }
 }
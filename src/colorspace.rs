@@ -0,0 +1,582 @@
+//! Conversions between RGB and other color models and color spaces.
+//!
+//! HSV and HSL are simple reparametrizations of whatever RGB values they are
+//! given. CIE XYZ, L*a*b* and Oklab conversions expect linear RGB. RGB color
+//! spaces are described by their primaries and white point, so that colors
+//! can be converted between sRGB, Display P3 and Rec. 2020 gamuts.
+
+use crate::color::Color;
+
+/// 3x3 matrix, stored row by row.
+pub type Matrix3 = [[f32; 3]; 3];
+
+/// Chromaticity coordinates of the CIE standard illuminant D65.
+pub const D65: (f32, f32) = (0.3127, 0.3290);
+
+/// Chromaticity coordinates of the CIE standard illuminant D50.
+pub const D50: (f32, f32) = (0.3457, 0.3585);
+
+/// Hue, saturation and value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsv {
+    /// Hue in degrees, in [0, 360)
+    pub h: f32,
+    /// Saturation in [0, 1]
+    pub s: f32,
+    /// Value in [0, 1]
+    pub v: f32,
+}
+
+/// Hue, saturation and lightness.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsl {
+    /// Hue in degrees, in [0, 360)
+    pub h: f32,
+    /// Saturation in [0, 1]
+    pub s: f32,
+    /// Lightness in [0, 1]
+    pub l: f32,
+}
+
+/// CIE 1931 XYZ tristimulus values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Xyz {
+    /// X
+    pub x: f32,
+    /// Y, the luminance
+    pub y: f32,
+    /// Z
+    pub z: f32,
+}
+
+/// CIE 1976 L*a*b* color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lab {
+    /// Lightness, 0 to 100
+    pub l: f32,
+    /// Green to red axis
+    pub a: f32,
+    /// Blue to yellow axis
+    pub b: f32,
+}
+
+/// Oklab color, a perceptual color space by Björn Ottosson.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Oklab {
+    /// Lightness, 0 to 1
+    pub l: f32,
+    /// Green to red axis
+    pub a: f32,
+    /// Blue to yellow axis
+    pub b: f32,
+}
+
+/// RGB color space defined by the chromaticities of its primaries and white.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RgbSpace {
+    /// Red primary
+    pub red: (f32, f32),
+    /// Green primary
+    pub green: (f32, f32),
+    /// Blue primary
+    pub blue: (f32, f32),
+    /// White point
+    pub white: (f32, f32),
+}
+
+/// sRGB and Rec. 709 primaries.
+pub const SRGB: RgbSpace = RgbSpace {
+    red: (0.64, 0.33),
+    green: (0.30, 0.60),
+    blue: (0.15, 0.06),
+    white: D65,
+};
+
+/// Display P3 primaries.
+pub const DISPLAY_P3: RgbSpace = RgbSpace {
+    red: (0.680, 0.320),
+    green: (0.265, 0.690),
+    blue: (0.150, 0.060),
+    white: D65,
+};
+
+/// Rec. 2020 primaries.
+pub const REC2020: RgbSpace = RgbSpace {
+    red: (0.708, 0.292),
+    green: (0.170, 0.797),
+    blue: (0.131, 0.046),
+    white: D65,
+};
+
+impl Hsv {
+    /// Convert RGB color to HSV.
+    pub fn from_rgb(color: Color<f32>) -> Self {
+        let (hue, min, max) = hue_min_max(color);
+        let s = if max > 0.0 { (max - min) / max } else { 0.0 };
+        Self { h: hue, s, v: max }
+    }
+
+    /// Convert to RGB color.
+    pub fn to_rgb(self) -> Color<f32> {
+        let chroma = self.v * self.s;
+        rgb_from_hue_chroma(self.h, chroma, self.v - chroma)
+    }
+}
+
+impl Hsl {
+    /// Convert RGB color to HSL.
+    pub fn from_rgb(color: Color<f32>) -> Self {
+        let (hue, min, max) = hue_min_max(color);
+        let l = 0.5 * (max + min);
+        let s = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        Self { h: hue, s, l }
+    }
+
+    /// Convert to RGB color.
+    pub fn to_rgb(self) -> Color<f32> {
+        let chroma = (1.0 - (2.0 * self.l - 1.0).abs()) * self.s;
+        rgb_from_hue_chroma(self.h, chroma, self.l - 0.5 * chroma)
+    }
+}
+
+/// Return hue in degrees, and minimum and maximum channel.
+fn hue_min_max(color: Color<f32>) -> (f32, f32, f32) {
+    let max = color.r.max(color.g).max(color.b);
+    let min = color.r.min(color.g).min(color.b);
+    let chroma = max - min;
+    let hue = if chroma == 0.0 {
+        0.0
+    } else if max == color.r {
+        60.0 * ((color.g - color.b) / chroma).rem_euclid(6.0)
+    } else if max == color.g {
+        60.0 * ((color.b - color.r) / chroma + 2.0)
+    } else {
+        60.0 * ((color.r - color.g) / chroma + 4.0)
+    };
+    (hue, min, max)
+}
+
+/// Return color with the given hue and chroma, with `offset` added to all channels.
+fn rgb_from_hue_chroma(hue: f32, chroma: f32, offset: f32) -> Color<f32> {
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    Color::new(r + offset, g + offset, b + offset)
+}
+
+impl Xyz {
+    /// Return XYZ of a white point with luminance 1.
+    pub fn white(chromaticity: (f32, f32)) -> Self {
+        let (x, y) = chromaticity;
+        Self {
+            x: x / y,
+            y: 1.0,
+            z: (1.0 - x - y) / y,
+        }
+    }
+
+    /// Convert linear sRGB color to XYZ.
+    pub fn from_linear_srgb(color: Color<f32>) -> Self {
+        SRGB.to_xyz(color)
+    }
+
+    /// Convert to linear sRGB color.
+    pub fn to_linear_srgb(self) -> Color<f32> {
+        SRGB.from_xyz(self)
+    }
+
+    /// Adapt color seen under one white point to how it appears under another.
+    ///
+    /// Uses the Bradford cone response model.
+    pub fn adapt(self, source_white: Xyz, target_white: Xyz) -> Self {
+        multiply_xyz(&bradford_matrix(source_white, target_white), self)
+    }
+}
+
+impl Lab {
+    /// Convert XYZ to L*a*b* relative to a reference white.
+    pub fn from_xyz(xyz: Xyz, white: Xyz) -> Self {
+        let fx = lab_f(xyz.x / white.x);
+        let fy = lab_f(xyz.y / white.y);
+        let fz = lab_f(xyz.z / white.z);
+        Self {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+
+    /// Convert to XYZ relative to a reference white.
+    pub fn to_xyz(self, white: Xyz) -> Xyz {
+        let fy = (self.l + 16.0) / 116.0;
+        let fx = fy + self.a / 500.0;
+        let fz = fy - self.b / 200.0;
+        Xyz {
+            x: white.x * lab_f_inverse(fx),
+            y: white.y * lab_f_inverse(fy),
+            z: white.z * lab_f_inverse(fz),
+        }
+    }
+
+    /// Convert linear sRGB color to L*a*b* relative to D65.
+    pub fn from_linear_srgb(color: Color<f32>) -> Self {
+        Self::from_xyz(Xyz::from_linear_srgb(color), Xyz::white(D65))
+    }
+
+    /// Convert to linear sRGB color, relative to D65.
+    pub fn to_linear_srgb(self) -> Color<f32> {
+        self.to_xyz(Xyz::white(D65)).to_linear_srgb()
+    }
+}
+
+const LAB_EPSILON: f32 = 216.0 / 24389.0;
+const LAB_KAPPA: f32 = 24389.0 / 27.0;
+
+fn lab_f(t: f32) -> f32 {
+    if t > LAB_EPSILON {
+        t.cbrt()
+    } else {
+        (LAB_KAPPA * t + 16.0) / 116.0
+    }
+}
+
+fn lab_f_inverse(f: f32) -> f32 {
+    let cube = f * f * f;
+    if cube > LAB_EPSILON {
+        cube
+    } else {
+        (116.0 * f - 16.0) / LAB_KAPPA
+    }
+}
+
+impl Oklab {
+    /// Convert linear sRGB color to Oklab.
+    pub fn from_linear_srgb(color: Color<f32>) -> Self {
+        let lms = multiply(
+            &[
+                [0.412_221_47, 0.536_332_55, 0.051_445_995],
+                [0.211_903_5, 0.680_699_5, 0.107_396_96],
+                [0.088_302_46, 0.281_718_85, 0.629_978_7],
+            ],
+            color,
+        );
+        let lms = Color::new(lms.r.cbrt(), lms.g.cbrt(), lms.b.cbrt());
+        let lab = multiply(
+            &[
+                [0.210_454_26, 0.793_617_8, -0.004_072_047],
+                [1.977_998_5, -2.428_592_2, 0.450_593_7],
+                [0.025_904_037, 0.782_771_77, -0.808_675_77],
+            ],
+            lms,
+        );
+        Self {
+            l: lab.r,
+            a: lab.g,
+            b: lab.b,
+        }
+    }
+
+    /// Convert to linear sRGB color.
+    pub fn to_linear_srgb(self) -> Color<f32> {
+        let lms = multiply(
+            &[
+                [1.0, 0.396_337_78, 0.215_803_76],
+                [1.0, -0.105_561_346, -0.063_854_17],
+                [1.0, -0.089_484_18, -1.291_485_5],
+            ],
+            Color::new(self.l, self.a, self.b),
+        );
+        let lms = Color::new(lms.r.powi(3), lms.g.powi(3), lms.b.powi(3));
+        multiply(
+            &[
+                [4.076_741_7, -3.307_711_6, 0.230_969_94],
+                [-1.268_438, 2.609_757_4, -0.341_319_38],
+                [-0.004_196_086_3, -0.703_418_6, 1.707_614_7],
+            ],
+            lms,
+        )
+    }
+}
+
+impl RgbSpace {
+    /// Matrix from linear RGB in this space to XYZ.
+    pub fn to_xyz_matrix(&self) -> Matrix3 {
+        let column = |(x, y): (f32, f32)| [x / y, 1.0, (1.0 - x - y) / y];
+        let (r, g, b) = (column(self.red), column(self.green), column(self.blue));
+        let primaries = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+        // Scale the primaries so that RGB (1, 1, 1) maps to the white point.
+        let white = Xyz::white(self.white);
+        let scale = multiply(&invert(&primaries), Color::new(white.x, white.y, white.z));
+        let scale = [scale.r, scale.g, scale.b];
+        let mut matrix = primaries;
+        for row in matrix.iter_mut() {
+            for (value, factor) in row.iter_mut().zip(scale.iter()) {
+                *value *= factor;
+            }
+        }
+        matrix
+    }
+
+    /// Convert linear RGB in this space to XYZ.
+    pub fn to_xyz(&self, color: Color<f32>) -> Xyz {
+        let xyz = multiply(&self.to_xyz_matrix(), color);
+        Xyz {
+            x: xyz.r,
+            y: xyz.g,
+            z: xyz.b,
+        }
+    }
+
+    /// Convert XYZ to linear RGB in this space.
+    pub fn from_xyz(&self, xyz: Xyz) -> Color<f32> {
+        multiply(
+            &invert(&self.to_xyz_matrix()),
+            Color::new(xyz.x, xyz.y, xyz.z),
+        )
+    }
+
+    /// Matrix converting linear RGB in this space to linear RGB in another.
+    ///
+    /// White points are matched with Bradford adaptation if they differ.
+    /// Colors outside the target gamut get negative channels.
+    pub fn conversion_matrix(&self, target: &RgbSpace) -> Matrix3 {
+        let mut matrix = self.to_xyz_matrix();
+        if self.white != target.white {
+            let adaptation = bradford_matrix(Xyz::white(self.white), Xyz::white(target.white));
+            matrix = matmul(&adaptation, &matrix);
+        }
+        matmul(&invert(&target.to_xyz_matrix()), &matrix)
+    }
+
+    /// Convert linear RGB in this space to linear RGB in another.
+    pub fn convert(&self, color: Color<f32>, target: &RgbSpace) -> Color<f32> {
+        multiply(&self.conversion_matrix(target), color)
+    }
+}
+
+/// Bradford chromatic adaptation matrix between two white points.
+pub fn bradford_matrix(source_white: Xyz, target_white: Xyz) -> Matrix3 {
+    const BRADFORD: Matrix3 = [
+        [0.8951, 0.2664, -0.1614],
+        [-0.7502, 1.7135, 0.0367],
+        [0.0389, -0.0685, 1.0296],
+    ];
+    let cone = |white: Xyz| multiply(&BRADFORD, Color::new(white.x, white.y, white.z));
+    let (source, target) = (cone(source_white), cone(target_white));
+    let scale = [
+        [target.r / source.r, 0.0, 0.0],
+        [0.0, target.g / source.g, 0.0],
+        [0.0, 0.0, target.b / source.b],
+    ];
+    matmul(&invert(&BRADFORD), &matmul(&scale, &BRADFORD))
+}
+
+/// Multiply a matrix with a color as column vector.
+pub fn multiply(matrix: &Matrix3, color: Color<f32>) -> Color<f32> {
+    let row = |i: usize| matrix[i][0] * color.r + matrix[i][1] * color.g + matrix[i][2] * color.b;
+    Color::new(row(0), row(1), row(2))
+}
+
+fn multiply_xyz(matrix: &Matrix3, xyz: Xyz) -> Xyz {
+    let result = multiply(matrix, Color::new(xyz.x, xyz.y, xyz.z));
+    Xyz {
+        x: result.r,
+        y: result.g,
+        z: result.b,
+    }
+}
+
+fn matmul(lhs: &Matrix3, rhs: &Matrix3) -> Matrix3 {
+    let mut result = [[0.0; 3]; 3];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| lhs[i][k] * rhs[k][j]).sum();
+        }
+    }
+    result
+}
+
+fn invert(m: &Matrix3) -> Matrix3 {
+    let cofactor =
+        |r1: usize, r2: usize, c1: usize, c2: usize| m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1];
+    let adjugate = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+    let determinant =
+        m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+    let mut result = adjugate;
+    for row in result.iter_mut() {
+        for value in row.iter_mut() {
+            *value /= determinant;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test_colorspace {
+    use crate::color::Color;
+
+    use super::*;
+
+    fn assert_matrix_eq(result: &Matrix3, expected: &Matrix3, epsilon: f32) {
+        for (result_row, expected_row) in result.iter().zip(expected.iter()) {
+            for (value, expected_value) in result_row.iter().zip(expected_row.iter()) {
+                assert_relative_eq!(value, expected_value, epsilon = epsilon);
+            }
+        }
+    }
+
+    #[test]
+    fn hsv_round_trip() {
+        let color = Color::new(0.5, 0.25, 0.75);
+        let hsv = Hsv::from_rgb(color);
+        assert_relative_eq!(hsv.h, 270.0);
+        assert_relative_eq!(hsv.s, 2.0 / 3.0);
+        assert_relative_eq!(hsv.v, 0.75);
+        assert_relative_eq!(hsv.to_rgb(), color, epsilon = 1e-6);
+
+        let hsv = Hsv::from_rgb(Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(hsv.h, 0.0);
+        let hsv = Hsv::from_rgb(Color::new(0.0, 1.0, 1.0));
+        assert_relative_eq!(hsv.h, 180.0);
+        let gray = Hsv::from_rgb(Color::new(0.4, 0.4, 0.4));
+        assert_relative_eq!(gray.s, 0.0);
+    }
+
+    #[test]
+    fn hsl_round_trip() {
+        let color = Color::new(0.5, 0.25, 0.75);
+        let hsl = Hsl::from_rgb(color);
+        assert_relative_eq!(hsl.h, 270.0);
+        assert_relative_eq!(hsl.s, 0.5);
+        assert_relative_eq!(hsl.l, 0.5);
+        assert_relative_eq!(hsl.to_rgb(), color, epsilon = 1e-6);
+
+        let hsl = Hsl {
+            h: 120.0,
+            s: 1.0,
+            l: 0.25,
+        };
+        assert_relative_eq!(hsl.to_rgb(), Color::new(0.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn srgb_to_xyz_matrix() {
+        // Reference matrix from Bruce Lindbloom, who derives the white point
+        // from tabulated XYZ rather than chromaticities, hence the tolerance.
+        let expected = [
+            [0.412_456_4, 0.357_576_1, 0.180_437_5],
+            [0.212_672_9, 0.715_152_2, 0.072_175],
+            [0.019_333_9, 0.119_192, 0.950_304_1],
+        ];
+        assert_matrix_eq(&SRGB.to_xyz_matrix(), &expected, 5e-4);
+        let white = Xyz::from_linear_srgb(Color::new(1.0, 1.0, 1.0));
+        assert_relative_eq!(white.x, 0.950_47, epsilon = 1e-4);
+        assert_relative_eq!(white.y, 1.0, epsilon = 1e-5);
+        assert_relative_eq!(white.z, 1.088_83, epsilon = 1e-3);
+        let color = Color::new(0.2, 0.5, 0.8);
+        assert_relative_eq!(
+            Xyz::from_linear_srgb(color).to_linear_srgb(),
+            color,
+            epsilon = 1e-5
+        );
+    }
+
+    #[test]
+    fn lab_reference_values() {
+        let red = Lab::from_linear_srgb(Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(red.l, 53.2408, epsilon = 2e-2);
+        assert_relative_eq!(red.a, 80.0925, epsilon = 5e-2);
+        assert_relative_eq!(red.b, 67.2032, epsilon = 5e-2);
+        let white = Lab::from_linear_srgb(Color::new(1.0, 1.0, 1.0));
+        assert_relative_eq!(white.l, 100.0, epsilon = 1e-3);
+        assert_relative_eq!(white.a, 0.0, epsilon = 1e-3);
+        let dark = Color::new(0.001, 0.002, 0.003);
+        assert_relative_eq!(
+            Lab::from_linear_srgb(dark).to_linear_srgb(),
+            dark,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn oklab_reference_values() {
+        // Values from Björn Ottosson's Oklab post.
+        let red = Oklab::from_linear_srgb(Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(red.l, 0.627_955, epsilon = 1e-4);
+        assert_relative_eq!(red.a, 0.224_863, epsilon = 1e-4);
+        assert_relative_eq!(red.b, 0.125_846, epsilon = 1e-4);
+        let white = Oklab::from_linear_srgb(Color::new(1.0, 1.0, 1.0));
+        assert_relative_eq!(white.l, 1.0, epsilon = 1e-4);
+        assert_relative_eq!(white.a, 0.0, epsilon = 1e-4);
+        let color = Color::new(0.2, 0.5, 0.8);
+        assert_relative_eq!(
+            Oklab::from_linear_srgb(color).to_linear_srgb(),
+            color,
+            epsilon = 1e-4
+        );
+    }
+
+    #[test]
+    fn bradford_d65_to_d50() {
+        // Reference matrix from Bruce Lindbloom, see above for the tolerance.
+        let expected = [
+            [1.047_811_2, 0.022_886_6, -0.050_127],
+            [0.029_542_4, 0.990_484_4, -0.017_049_1],
+            [-0.009_234_5, 0.015_043_6, 0.752_131_6],
+        ];
+        let result = bradford_matrix(Xyz::white(D65), Xyz::white(D50));
+        assert_matrix_eq(&result, &expected, 5e-4);
+        let adapted = Xyz::white(D65).adapt(Xyz::white(D65), Xyz::white(D50));
+        assert_relative_eq!(adapted.x, Xyz::white(D50).x, epsilon = 1e-5);
+        assert_relative_eq!(adapted.z, Xyz::white(D50).z, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn gamut_conversions() {
+        // Rec. 709 to Rec. 2020 matrix from ITU-R BT.2087.
+        let expected = [
+            [0.6274, 0.3293, 0.0433],
+            [0.0691, 0.9195, 0.0114],
+            [0.0164, 0.0880, 0.8956],
+        ];
+        assert_matrix_eq(&SRGB.conversion_matrix(&REC2020), &expected, 1e-4);
+        let red_in_p3 = SRGB.convert(Color::new(1.0, 0.0, 0.0), &DISPLAY_P3);
+        assert_relative_eq!(
+            red_in_p3,
+            Color::new(0.8225, 0.0332, 0.0171),
+            epsilon = 1e-4
+        );
+        let white = SRGB.convert(Color::new(1.0, 1.0, 1.0), &DISPLAY_P3);
+        assert_relative_eq!(white, Color::new(1.0, 1.0, 1.0), epsilon = 1e-5);
+        // Pure P3 green lies outside the sRGB gamut.
+        let green = DISPLAY_P3.convert(Color::new(0.0, 1.0, 0.0), &SRGB);
+        assert!(green.r < 0.0 && green.b < 0.0);
+    }
+}
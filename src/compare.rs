@@ -0,0 +1,438 @@
+//! Comparison of canvases, for regression tests against reference renders.
+//!
+//! Provides the usual metrics (MSE, PSNR and SSIM) and a perceptual error
+//! after NVIDIA's FLIP. Per pixel errors can be turned into a heatmap to
+//! see where two images differ. In tests, [`assert_canvas_approx_eq!`]
+//! compares canvases pixel by pixel using the `approx` traits of [`Color`].
+//!
+//! [`assert_canvas_approx_eq!`]: crate::assert_canvas_approx_eq
+
+use approx::{AbsDiffEq, RelativeEq};
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::colorspace::Lab;
+use crate::postprocess::gaussian_blur;
+
+/// Assert that two canvases have the same size and approximately equal pixels.
+///
+/// Takes an optional `epsilon` for the absolute difference and optional
+/// `max_relative` for the relative difference, like `assert_relative_eq!`.
+/// On failure the number of differing pixels and the first few are
+/// reported.
+///
+/// ```
+/// use rust_raytracer::assert_canvas_approx_eq;
+/// use rust_raytracer::canvas::Canvas;
+/// use rust_raytracer::color::Color;
+///
+/// let mut a = Canvas::new(2, 2);
+/// a.fill(Color::new(0.5, 0.5, 0.5));
+/// let mut b = Canvas::new(2, 2);
+/// b.fill(Color::new(0.501, 0.5, 0.5));
+/// assert_canvas_approx_eq!(a, b, epsilon = 0.01);
+/// ```
+#[macro_export]
+macro_rules! assert_canvas_approx_eq {
+    ($left:expr, $right:expr $(, $name:ident = $value:expr)* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut tolerance = $crate::compare::Tolerance::default();
+        $(tolerance.$name = $value;)*
+        if let Err(message) = $crate::compare::check_approx_eq(&$left, &$right, tolerance) {
+            panic!("assertion failed: canvases differ\n{}", message);
+        }
+    }};
+}
+
+/// Tolerances used by [`assert_canvas_approx_eq!`](crate::assert_canvas_approx_eq).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Largest absolute difference per channel
+    pub epsilon: f32,
+    /// Largest difference per channel relative to the larger value
+    pub max_relative: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            epsilon: Color::<f32>::default_epsilon(),
+            max_relative: Color::<f32>::default_max_relative(),
+        }
+    }
+}
+
+/// Compare canvases pixel by pixel, describing the differences if there are any.
+pub fn check_approx_eq(left: &Canvas, right: &Canvas, tolerance: Tolerance) -> Result<(), String> {
+    if left.height != right.height || left.width != right.width {
+        return Err(format!(
+            "size {}x{} differs from {}x{}",
+            left.height, left.width, right.height, right.width
+        ));
+    }
+    let mismatches: Vec<usize> = (0..left.pixels.len())
+        .filter(|&index| {
+            !left.pixels[index].relative_eq(
+                &right.pixels[index],
+                tolerance.epsilon,
+                tolerance.max_relative,
+            )
+        })
+        .collect();
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!(
+        "{} of {} pixels differ with {:?}",
+        mismatches.len(),
+        left.pixels.len(),
+        tolerance
+    );
+    for &index in mismatches.iter().take(5) {
+        message += &format!(
+            "\n  ({}, {}): {} != {}",
+            index / left.width,
+            index % left.width,
+            left.pixels[index],
+            right.pixels[index]
+        );
+    }
+    Err(message)
+}
+
+/// Return the absolute difference of every channel.
+///
+/// # Panics
+///
+/// All functions comparing two canvases panic if their sizes differ.
+pub fn absolute_difference(a: &Canvas, b: &Canvas) -> Canvas {
+    assert_same_size(a, b);
+    let mut result = Canvas::new(a.height, a.width);
+    for ((target, a), b) in result
+        .pixels
+        .iter_mut()
+        .zip(a.pixels.iter())
+        .zip(b.pixels.iter())
+    {
+        *target = Color::new((a.r - b.r).abs(), (a.g - b.g).abs(), (a.b - b.b).abs());
+    }
+    result
+}
+
+fn assert_same_size(a: &Canvas, b: &Canvas) {
+    assert!(
+        a.height == b.height && a.width == b.width,
+        "canvases differ in size"
+    );
+}
+
+/// Mean squared error over all channels.
+pub fn mean_squared_error(a: &Canvas, b: &Canvas) -> f32 {
+    let difference = absolute_difference(a, b);
+    let sum: f64 = difference
+        .pixels
+        .iter()
+        .map(|d| (d.r * d.r + d.g * d.g + d.b * d.b) as f64)
+        .sum();
+    (sum / (3 * a.pixels.len()).max(1) as f64) as f32
+}
+
+/// Peak signal to noise ratio in decibels, for values up to `peak`.
+///
+/// Identical canvases give infinity.
+pub fn psnr(a: &Canvas, b: &Canvas, peak: f32) -> f32 {
+    let error = mean_squared_error(a, b);
+    if error == 0.0 {
+        f32::INFINITY
+    } else {
+        10.0 * (peak * peak / error).log10()
+    }
+}
+
+/// Map of structural similarity of the luminance, 1 where the canvases match.
+///
+/// Uses the Gaussian window with sigma 1.5 and the constants of Wang et al.
+/// (2004), for values in [0, 1].
+pub fn ssim_map(a: &Canvas, b: &Canvas) -> Vec<f32> {
+    assert_same_size(a, b);
+    const C1: f32 = 0.01 * 0.01;
+    const C2: f32 = 0.03 * 0.03;
+    let luminance = |canvas: &Canvas| -> Vec<f32> {
+        canvas
+            .pixels
+            .iter()
+            .map(|pixel| pixel.luminance())
+            .collect()
+    };
+    let (la, lb) = (luminance(a), luminance(b));
+    let blur = |values: Vec<f32>| -> Vec<f32> {
+        let mut canvas = Canvas::new(a.height, a.width);
+        for (pixel, value) in canvas.pixels.iter_mut().zip(values) {
+            *pixel = Color::new(value, 0.0, 0.0);
+        }
+        gaussian_blur(&canvas, 1.5)
+            .pixels
+            .iter()
+            .map(|pixel| pixel.r)
+            .collect()
+    };
+    let mean_a = blur(la.clone());
+    let mean_b = blur(lb.clone());
+    let square_a = blur(la.iter().map(|x| x * x).collect());
+    let square_b = blur(lb.iter().map(|x| x * x).collect());
+    let product = blur(la.iter().zip(lb.iter()).map(|(x, y)| x * y).collect());
+    (0..la.len())
+        .map(|i| {
+            let (mu_a, mu_b) = (mean_a[i], mean_b[i]);
+            let variance_a = square_a[i] - mu_a * mu_a;
+            let variance_b = square_b[i] - mu_b * mu_b;
+            let covariance = product[i] - mu_a * mu_b;
+            ((2.0 * mu_a * mu_b + C1) * (2.0 * covariance + C2))
+                / ((mu_a * mu_a + mu_b * mu_b + C1) * (variance_a + variance_b + C2))
+        })
+        .collect()
+}
+
+/// Mean structural similarity, 1 for identical canvases.
+pub fn ssim(a: &Canvas, b: &Canvas) -> f32 {
+    mean(&ssim_map(a, b))
+}
+
+/// Per pixel perceptual error in [0, 1], after FLIP by Andersson et al. (2020).
+///
+/// A simplified version of the LDR algorithm: both canvases are clamped to
+/// [0, 1] and blurred slightly to mimic the eye's contrast sensitivity, then
+/// the color error is the HyAB distance in L*a*b*, normalized by the
+/// distance between green and blue. Differences in edges, measured as
+/// luminance gradients, raise the color error towards 1. Values are not
+/// comparable to the reference FLIP implementation.
+pub fn flip_map(a: &Canvas, b: &Canvas) -> Vec<f32> {
+    assert_same_size(a, b);
+    let prepare = |canvas: &Canvas| {
+        let mut clamped = canvas.clone();
+        for pixel in clamped.pixels.iter_mut() {
+            *pixel = Color::new(
+                pixel.r.clamp(0.0, 1.0),
+                pixel.g.clamp(0.0, 1.0),
+                pixel.b.clamp(0.0, 1.0),
+            );
+        }
+        clamped
+    };
+    let (a, b) = (prepare(a), prepare(b));
+    let (blurred_a, blurred_b) = (gaussian_blur(&a, 0.5), gaussian_blur(&b, 0.5));
+    let maximum = hyab(
+        Lab::from_linear_srgb(Color::new(0.0, 1.0, 0.0)),
+        Lab::from_linear_srgb(Color::new(0.0, 0.0, 1.0)),
+    )
+    .powf(0.7);
+    let (edges_a, edges_b) = (gradient_magnitude(&a), gradient_magnitude(&b));
+    (0..a.pixels.len())
+        .map(|i| {
+            let color = hyab(
+                Lab::from_linear_srgb(blurred_a.pixels[i]),
+                Lab::from_linear_srgb(blurred_b.pixels[i]),
+            );
+            let color = (color.powf(0.7) / maximum).min(1.0);
+            let feature = ((edges_a[i] - edges_b[i]).abs() / 2.0_f32.sqrt())
+                .sqrt()
+                .min(1.0);
+            color.powf(1.0 - feature)
+        })
+        .collect()
+}
+
+/// Mean perceptual error, 0 for identical canvases.
+pub fn flip(a: &Canvas, b: &Canvas) -> f32 {
+    mean(&flip_map(a, b))
+}
+
+/// Hybrid distance, Manhattan for lightness and Euclidean for chroma.
+fn hyab(a: Lab, b: Lab) -> f32 {
+    (a.l - b.l).abs() + ((a.a - b.a).powi(2) + (a.b - b.b).powi(2)).sqrt()
+}
+
+/// Magnitude of the central difference gradient of the luminance.
+fn gradient_magnitude(canvas: &Canvas) -> Vec<f32> {
+    let value = |row: isize, column: isize| {
+        let row = row.clamp(0, canvas.height as isize - 1) as usize;
+        let column = column.clamp(0, canvas.width as isize - 1) as usize;
+        canvas[(row, column)].luminance()
+    };
+    let mut result = Vec::with_capacity(canvas.pixels.len());
+    for row in 0..canvas.height as isize {
+        for column in 0..canvas.width as isize {
+            let dx = 0.5 * (value(row, column + 1) - value(row, column - 1));
+            let dy = 0.5 * (value(row + 1, column) - value(row - 1, column));
+            result.push((dx * dx + dy * dy).sqrt());
+        }
+    }
+    result
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().map(|&value| value as f64).sum::<f64>() as f32 / values.len().max(1) as f32
+}
+
+/// Color an error map from black through red and yellow to white.
+///
+/// Errors are divided by `maximum` first, and values at or above it are white.
+///
+/// # Panics
+///
+/// Panics if there is not one error per pixel.
+pub fn heatmap(errors: &[f32], height: usize, width: usize, maximum: f32) -> Canvas {
+    assert_eq!(errors.len(), height * width, "one error per pixel");
+    let mut canvas = Canvas::new(height, width);
+    for (pixel, error) in canvas.pixels.iter_mut().zip(errors.iter()) {
+        let t = (error / maximum).clamp(0.0, 1.0) * 3.0;
+        *pixel = Color::new(t.min(1.0), (t - 1.0).clamp(0.0, 1.0), (t - 2.0).max(0.0));
+    }
+    canvas
+}
+
+/// Heatmap of the largest absolute channel difference of every pixel.
+pub fn difference_heatmap(a: &Canvas, b: &Canvas, maximum: f32) -> Canvas {
+    let difference = absolute_difference(a, b);
+    let errors: Vec<f32> = difference
+        .pixels
+        .iter()
+        .map(|d| d.r.max(d.g).max(d.b))
+        .collect();
+    heatmap(&errors, a.height, a.width, maximum)
+}
+
+#[cfg(test)]
+mod test_compare {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::rng::Pcg32;
+
+    use super::*;
+
+    fn gray(value: f32) -> Color<f32> {
+        Color::new(value, value, value)
+    }
+
+    /// Horizontal gradient with a bright square in the middle.
+    fn scene() -> Canvas {
+        let mut canvas = Canvas::new(16, 16);
+        for row in 0..16 {
+            for column in 0..16 {
+                let inside = (5..11).contains(&row) && (5..11).contains(&column);
+                canvas[(row, column)] = if inside {
+                    Color::new(0.9, 0.8, 0.2)
+                } else {
+                    gray(column as f32 / 16.0)
+                };
+            }
+        }
+        canvas
+    }
+
+    fn with_noise(canvas: &Canvas, amount: f32) -> Canvas {
+        let mut rng = Pcg32::new(5);
+        let mut result = canvas.clone();
+        for pixel in result.pixels.iter_mut() {
+            *pixel = *pixel + gray(amount * (rng.next_f32() - 0.5));
+        }
+        result
+    }
+
+    #[test]
+    fn identical_canvases() {
+        let canvas = scene();
+        assert_relative_eq!(mean_squared_error(&canvas, &canvas), 0.0);
+        assert!(psnr(&canvas, &canvas, 1.0).is_infinite());
+        assert_relative_eq!(ssim(&canvas, &canvas), 1.0, epsilon = 1e-5);
+        assert_relative_eq!(flip(&canvas, &canvas), 0.0);
+    }
+
+    #[test]
+    fn error_metrics() {
+        let a = Canvas::new(2, 2);
+        let mut b = Canvas::new(2, 2);
+        b.fill(Color::new(0.5, 0.5, 0.5));
+        b[(0, 0)] = Color::new(0.5, 0.5, -0.5);
+        assert_relative_eq!(absolute_difference(&a, &b)[(0, 0)], gray(0.5));
+        assert_relative_eq!(mean_squared_error(&a, &b), 0.25);
+        assert_relative_eq!(psnr(&a, &b, 1.0), 6.0206, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn more_noise_is_less_similar() {
+        let canvas = scene();
+        let (slight, heavy) = (with_noise(&canvas, 0.05), with_noise(&canvas, 0.4));
+        assert!(ssim(&canvas, &slight) < 1.0);
+        assert!(ssim(&canvas, &heavy) < ssim(&canvas, &slight));
+        assert!(psnr(&canvas, &heavy, 1.0) < psnr(&canvas, &slight, 1.0));
+        assert!(flip(&canvas, &slight) > 0.0);
+        assert!(flip(&canvas, &heavy) > flip(&canvas, &slight));
+    }
+
+    #[test]
+    fn flip_sees_color_and_edges() {
+        let canvas = scene();
+        // A missing square is far worse than a slightly different one.
+        let mut missing = canvas.clone();
+        let mut tinted = canvas.clone();
+        for row in 5..11 {
+            for column in 5..11 {
+                missing[(row, column)] = gray(column as f32 / 16.0);
+                tinted[(row, column)] = Color::new(0.88, 0.8, 0.22);
+            }
+        }
+        let map = flip_map(&canvas, &missing);
+        assert!(map[8 * 16 + 8] > 0.3);
+        assert_relative_eq!(map[0], 0.0);
+        assert!(flip(&canvas, &missing) > 4.0 * flip(&canvas, &tinted));
+        assert!(map.iter().all(|error| (0.0..=1.0).contains(error)));
+    }
+
+    #[test]
+    fn heatmap_colors() {
+        let canvas = heatmap(&[0.0, 1.0, 2.0, 3.0, 6.0], 1, 5, 3.0);
+        assert_relative_eq!(canvas[(0, 0)], gray(0.0));
+        assert_relative_eq!(canvas[(0, 1)], Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(canvas[(0, 2)], Color::new(1.0, 1.0, 0.0));
+        assert_relative_eq!(canvas[(0, 3)], gray(1.0));
+        assert_relative_eq!(canvas[(0, 4)], gray(1.0));
+        let mut b = Canvas::new(1, 2);
+        b[(0, 1)] = Color::new(0.0, 0.5, 0.0);
+        let diff = difference_heatmap(&Canvas::new(1, 2), &b, 1.5);
+        assert_relative_eq!(diff[(0, 0)], gray(0.0));
+        assert_relative_eq!(diff[(0, 1)], Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn assert_macro_passes_within_tolerance() {
+        let canvas = scene();
+        assert_canvas_approx_eq!(canvas, canvas.clone());
+        let shifted = with_noise(&canvas, 0.01);
+        assert_canvas_approx_eq!(canvas, shifted, epsilon = 0.006);
+        let mut brighter = canvas.clone();
+        for pixel in brighter.pixels.iter_mut() {
+            *pixel = *pixel * 1.05;
+        }
+        assert_canvas_approx_eq!(canvas, brighter, max_relative = 0.05);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixels differ")]
+    fn assert_macro_fails_outside_tolerance() {
+        let canvas = scene();
+        assert_canvas_approx_eq!(canvas, with_noise(&canvas, 0.1), epsilon = 0.01);
+    }
+
+    #[test]
+    fn failure_report() {
+        let a = Canvas::new(2, 3);
+        let mut b = Canvas::new(2, 3);
+        b[(1, 2)] = gray(1.0);
+        let message = check_approx_eq(&a, &b, Tolerance::default()).unwrap_err();
+        assert!(message.starts_with("1 of 6 pixels differ"));
+        assert!(message.contains("(1, 2)"));
+        let message = check_approx_eq(&a, &Canvas::new(3, 2), Tolerance::default()).unwrap_err();
+        assert!(message == "size 2x3 differs from 3x2");
+    }
+}
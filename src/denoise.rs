@@ -0,0 +1,387 @@
+//! Edge-aware denoising of rendered canvases.
+//!
+//! Implements the edge-avoiding à-trous wavelet filter by Dammertz et al.
+//! (2010): a 5x5 B3 spline kernel is applied repeatedly with growing gaps
+//! between taps, and every tap is weighted down when its color, normal,
+//! albedo or depth differs from the center pixel. The variance driven mode
+//! follows SVGF (Schied et al. 2017) and scales the color weight by the
+//! sample variance of each pixel, so converged pixels are left alone.
+
+use crate::aov::{Aov, AovBuffer, RenderPasses};
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+/// Taps of the B3 spline kernel.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Buffers guiding the filter, all optional.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Guides<'a> {
+    /// Shading normals
+    pub normal: Option<&'a AovBuffer>,
+    /// Surface albedo
+    pub albedo: Option<&'a AovBuffer>,
+    /// Camera depth
+    pub depth: Option<&'a AovBuffer>,
+}
+
+impl<'a> Guides<'a> {
+    /// Use whichever guide buffers were rendered.
+    pub fn from_passes(passes: &'a RenderPasses) -> Self {
+        Self {
+            normal: passes.aov(Aov::Normal),
+            albedo: passes.aov(Aov::Albedo),
+            depth: passes.aov(Aov::Depth),
+        }
+    }
+}
+
+/// À-trous wavelet denoiser.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    /// Number of filter passes, each doubling the gap between taps
+    pub iterations: usize,
+    /// Color difference at which taps lose most weight
+    pub sigma_color: f32,
+    /// Exponent on the cosine between normals
+    pub sigma_normal: f32,
+    /// Albedo difference at which taps lose most weight
+    pub sigma_albedo: f32,
+    /// Relative depth difference at which taps lose most weight
+    pub sigma_depth: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 0.5,
+            sigma_normal: 64.0,
+            sigma_albedo: 0.1,
+            sigma_depth: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    /// Set number of filter passes.
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Set color sensitivity, or in variance driven mode the number of standard deviations.
+    pub fn with_sigma_color(mut self, sigma_color: f32) -> Self {
+        self.sigma_color = sigma_color;
+        self
+    }
+
+    /// Denoise a canvas, using the color difference to find edges.
+    ///
+    /// The color sensitivity is halved every pass, as later passes see
+    /// already smoothed colors.
+    pub fn denoise(&self, canvas: &Canvas, guides: &Guides) -> Canvas {
+        let mut current = canvas.clone();
+        let mut sigma = self.sigma_color;
+        for iteration in 0..self.iterations {
+            let color_weight = |center: Color<f32>, tap: Color<f32>, _: usize| {
+                let difference = center - tap;
+                let distance = difference.r * difference.r
+                    + difference.g * difference.g
+                    + difference.b * difference.b;
+                (-distance / (sigma * sigma)).exp()
+            };
+            current = self.pass(&current, guides, 1 << iteration, color_weight).0;
+            sigma *= 0.5;
+        }
+        current
+    }
+
+    /// Denoise a canvas given the variance of every pixel's estimate.
+    ///
+    /// The variance is of the pixel mean, that is the luminance sample
+    /// variance divided by the number of samples. Taps whose luminance
+    /// differs by more than `sigma_color` standard deviations lose most of
+    /// their weight. The variance is filtered along with the colors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is not one variance per pixel.
+    pub fn denoise_with_variance(
+        &self,
+        canvas: &Canvas,
+        variance: &[f32],
+        guides: &Guides,
+    ) -> Canvas {
+        assert_eq!(
+            canvas.pixels.len(),
+            variance.len(),
+            "one variance per pixel"
+        );
+        let mut current = canvas.clone();
+        let mut variance = variance.to_vec();
+        for iteration in 0..self.iterations {
+            let sigma = self.sigma_color;
+            let deviation = variance.clone();
+            let color_weight = |center: Color<f32>, tap: Color<f32>, index: usize| {
+                let difference = (center.luminance() - tap.luminance()).abs();
+                (-difference / (sigma * deviation[index].max(0.0).sqrt() + 1e-6)).exp()
+            };
+            let (filtered, weights) = self.pass(&current, guides, 1 << iteration, color_weight);
+            variance = filter_variance(&current, &variance, &weights, 1 << iteration);
+            current = filtered;
+        }
+        current
+    }
+
+    /// Apply one filter pass, returning the result and the weights used.
+    fn pass<F>(
+        &self,
+        canvas: &Canvas,
+        guides: &Guides,
+        step: usize,
+        color_weight: F,
+    ) -> (Canvas, Vec<[f32; 25]>)
+    where
+        F: Fn(Color<f32>, Color<f32>, usize) -> f32,
+    {
+        let mut result = Canvas::new(canvas.height, canvas.width);
+        let mut all_weights = Vec::with_capacity(canvas.pixels.len());
+        for row in 0..canvas.height {
+            for column in 0..canvas.width {
+                let center = canvas[(row, column)];
+                let mut sum = Color::new(0.0, 0.0, 0.0);
+                let mut total = 0.0;
+                let mut weights = [0.0; 25];
+                for (i, kernel_row) in KERNEL.iter().enumerate() {
+                    for (j, kernel_column) in KERNEL.iter().enumerate() {
+                        let tap_row = row as isize + (i as isize - 2) * step as isize;
+                        let tap_column = column as isize + (j as isize - 2) * step as isize;
+                        if tap_row < 0 || tap_column < 0 {
+                            continue;
+                        }
+                        let (tap_row, tap_column) = (tap_row as usize, tap_column as usize);
+                        let tap = match canvas.get(tap_row, tap_column) {
+                            Some(tap) => *tap,
+                            None => continue,
+                        };
+                        let weight = kernel_row
+                            * kernel_column
+                            * color_weight(center, tap, row * canvas.width + column)
+                            * self.guide_weight(guides, (row, column), (tap_row, tap_column), step);
+                        weights[i * 5 + j] = weight;
+                        sum = sum + tap * weight;
+                        total += weight;
+                    }
+                }
+                if total > 0.0 {
+                    result[(row, column)] = sum * (1.0 / total);
+                    all_weights.push(weights.map(|weight| weight / total));
+                } else {
+                    // Guides can reject even the center, for example a zero
+                    // normal where nothing was hit.
+                    result[(row, column)] = center;
+                    weights = [0.0; 25];
+                    weights[12] = 1.0;
+                    all_weights.push(weights);
+                }
+            }
+        }
+        (result, all_weights)
+    }
+
+    fn guide_weight(
+        &self,
+        guides: &Guides,
+        center: (usize, usize),
+        tap: (usize, usize),
+        step: usize,
+    ) -> f32 {
+        let mut weight = 1.0;
+        let values = |buffer| guide_values(buffer, center, tap);
+        if let Some(buffer) = guides.normal {
+            let (a, b) = values(buffer);
+            let cosine: f32 = a.iter().zip(b.iter()).map(|(a, b)| a * b).sum();
+            weight *= cosine.max(0.0).powf(self.sigma_normal);
+        }
+        if let Some(buffer) = guides.albedo {
+            let (a, b) = values(buffer);
+            let distance: f32 = a.iter().zip(b.iter()).map(|(a, b)| (a - b) * (a - b)).sum();
+            weight *= (-distance / (self.sigma_albedo * self.sigma_albedo)).exp();
+        }
+        if let Some(buffer) = guides.depth {
+            let (a, b) = values(buffer);
+            if let (Some(a), Some(b)) = (a.first(), b.first()) {
+                // Depth changes over larger distances as the taps spread out.
+                let scale = self.sigma_depth * a.abs().max(1e-6) * step as f32;
+                weight *= (-(a - b).abs() / scale).exp();
+            }
+        }
+        weight
+    }
+}
+
+/// Return guide values at the center and at a tap.
+fn guide_values(
+    buffer: &AovBuffer,
+    center: (usize, usize),
+    tap: (usize, usize),
+) -> (&[f32], &[f32]) {
+    (
+        buffer.get(center.0, center.1).unwrap_or(&[]),
+        buffer.get(tap.0, tap.1).unwrap_or(&[]),
+    )
+}
+
+/// Filter the variance with the squared weights of a pass.
+fn filter_variance(
+    canvas: &Canvas,
+    variance: &[f32],
+    weights: &[[f32; 25]],
+    step: usize,
+) -> Vec<f32> {
+    let mut result = vec![0.0; variance.len()];
+    for row in 0..canvas.height {
+        for column in 0..canvas.width {
+            let index = row * canvas.width + column;
+            let mut sum = 0.0;
+            for (tap, weight) in weights[index].iter().enumerate() {
+                if *weight == 0.0 {
+                    continue;
+                }
+                let tap_row = row + (tap / 5) * step - 2 * step;
+                let tap_column = column + (tap % 5) * step - 2 * step;
+                sum += weight * weight * variance[tap_row * canvas.width + tap_column];
+            }
+            result[index] = sum;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test_denoise {
+    use crate::aov::{Aov, RenderPasses};
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::rng::Pcg32;
+
+    use super::*;
+
+    const SIZE: usize = 24;
+
+    /// Scene of two walls meeting in the middle, with a bright left wall.
+    fn reference() -> RenderPasses {
+        let mut passes = RenderPasses::new(SIZE, SIZE, &[Aov::Normal, Aov::Albedo, Aov::Depth]);
+        for row in 0..SIZE {
+            for column in 0..SIZE {
+                let left = column < SIZE / 2;
+                let shade = 0.3 + 0.4 * row as f32 / SIZE as f32;
+                passes.beauty[(row, column)] = if left {
+                    Color::new(0.9, 0.5, 0.2) * shade
+                } else {
+                    Color::new(0.1, 0.2, 0.6) * shade
+                };
+                let normal = if left {
+                    [1.0, 0.0, 0.0]
+                } else {
+                    [0.0, 0.0, 1.0]
+                };
+                passes.set(row, column, Aov::Normal, &normal);
+                let albedo = if left {
+                    [0.9, 0.5, 0.2]
+                } else {
+                    [0.1, 0.2, 0.6]
+                };
+                passes.set(row, column, Aov::Albedo, &albedo);
+                passes.set(row, column, Aov::Depth, &[5.0]);
+            }
+        }
+        passes
+    }
+
+    /// Average a few samples that are uniform between zero and twice the reference.
+    fn noisy(reference: &Canvas, samples: usize) -> (Canvas, Vec<f32>) {
+        let mut rng = Pcg32::new(3);
+        let mut canvas = Canvas::new(reference.height, reference.width);
+        let mut variance = vec![0.0; reference.pixels.len()];
+        for (index, pixel) in canvas.pixels.iter_mut().enumerate() {
+            let values: Vec<Color<f32>> = (0..samples)
+                .map(|_| reference.pixels[index] * (2.0 * rng.next_f32()))
+                .collect();
+            let mean = values
+                .iter()
+                .fold(Color::new(0.0, 0.0, 0.0), |sum, value| sum + *value)
+                * (1.0 / samples as f32);
+            let spread: f32 = values
+                .iter()
+                .map(|value| (value.luminance() - mean.luminance()).powi(2))
+                .sum::<f32>()
+                / (samples - 1) as f32;
+            *pixel = mean;
+            variance[index] = spread / samples as f32;
+        }
+        (canvas, variance)
+    }
+
+    fn mean_squared_error(a: &Canvas, b: &Canvas) -> f32 {
+        let sum: f32 = a
+            .pixels
+            .iter()
+            .zip(b.pixels.iter())
+            .map(|(a, b)| {
+                let difference = *a - *b;
+                difference.r * difference.r
+                    + difference.g * difference.g
+                    + difference.b * difference.b
+            })
+            .sum();
+        sum / a.pixels.len() as f32
+    }
+
+    #[test]
+    fn guided_denoise_reduces_error() {
+        let reference = reference();
+        let (input, _) = noisy(&reference.beauty, 4);
+        let guides = Guides::from_passes(&reference);
+        let denoised = Denoiser::default().denoise(&input, &guides);
+        let before = mean_squared_error(&input, &reference.beauty);
+        let after = mean_squared_error(&denoised, &reference.beauty);
+        assert!(after < before / 4.0, "{} vs {}", after, before);
+        // The edge between the walls stays sharp.
+        let left = denoised[(SIZE / 2, SIZE / 2 - 1)];
+        let right = denoised[(SIZE / 2, SIZE / 2)];
+        assert!(left.r > 0.3 && right.r < 0.1);
+    }
+
+    #[test]
+    fn variance_driven_denoise_reduces_error() {
+        let reference = reference();
+        let (input, variance) = noisy(&reference.beauty, 4);
+        let guides = Guides::from_passes(&reference);
+        let denoiser = Denoiser::default().with_sigma_color(4.0);
+        let denoised = denoiser.denoise_with_variance(&input, &variance, &guides);
+        let before = mean_squared_error(&input, &reference.beauty);
+        let after = mean_squared_error(&denoised, &reference.beauty);
+        assert!(after < before / 4.0, "{} vs {}", after, before);
+    }
+
+    #[test]
+    fn zero_variance_leaves_pixels() {
+        let reference = reference();
+        let (input, _) = noisy(&reference.beauty, 4);
+        let variance = vec![0.0; input.pixels.len()];
+        let denoised =
+            Denoiser::default().denoise_with_variance(&input, &variance, &Guides::default());
+        assert_relative_eq!(denoised[(3, 4)], input[(3, 4)], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn pixels_without_normal_keep_color() {
+        let passes = RenderPasses::new(3, 3, &[Aov::Normal]);
+        let mut input = Canvas::new(3, 3);
+        input[(1, 1)] = Color::new(1.0, 0.0, 0.0);
+        let denoised = Denoiser::default().denoise(&input, &Guides::from_passes(&passes));
+        assert_relative_eq!(denoised[(1, 1)], Color::new(1.0, 0.0, 0.0));
+    }
+}
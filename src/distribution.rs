@@ -0,0 +1,191 @@
+//! Piecewise constant distributions for importance sampling.
+//!
+//! Distributions are built from non-negative function values and map uniform
+//! random numbers in [0, 1) to samples proportional to those values.
+
+/// Piecewise constant distribution over [0, 1).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution1D {
+    /// Function value of each segment
+    pub func: Vec<f32>,
+    /// Cumulative distribution, one longer than `func`
+    pub cdf: Vec<f32>,
+    /// Integral of the function over [0, 1)
+    pub integral: f32,
+}
+
+impl Distribution1D {
+    /// Construct a new distribution from function values.
+    ///
+    /// Negative values are treated as zero. If all values are zero, the
+    /// distribution falls back to uniform.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no function values.
+    pub fn new(func: &[f32]) -> Self {
+        assert!(!func.is_empty(), "empty distribution");
+        let n = func.len();
+        let func: Vec<f32> = func.iter().map(|value| value.max(0.0)).collect();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f32;
+        }
+        let integral = cdf[n];
+        if integral > 0.0 {
+            for value in cdf.iter_mut() {
+                *value /= integral;
+            }
+        } else {
+            for (i, value) in cdf.iter_mut().enumerate() {
+                *value = i as f32 / n as f32;
+            }
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    /// Number of segments.
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// Map uniform sample to a position in [0, 1).
+    ///
+    /// Returns the position, its probability density and the segment index.
+    pub fn sample_continuous(&self, u: f32) -> (f32, f32, usize) {
+        // Last cdf entry not larger than u.
+        let index = self
+            .cdf
+            .partition_point(|&value| value <= u)
+            .clamp(1, self.count())
+            - 1;
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+        let position = ((index as f32 + offset) / self.count() as f32).min(1.0 - f32::EPSILON);
+        (position, self.pdf(index), index)
+    }
+
+    /// Probability density of the given segment.
+    pub fn pdf(&self, index: usize) -> f32 {
+        if self.integral > 0.0 {
+            self.func[index] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Piecewise constant distribution over [0, 1) x [0, 1).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution2D {
+    /// Distribution along u for every row
+    pub conditional: Vec<Distribution1D>,
+    /// Distribution over the rows
+    pub marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Construct a new distribution from row-major function values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is empty or does not match the number of values.
+    pub fn new(func: &[f32], width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "empty distribution");
+        assert_eq!(func.len(), width * height, "distribution size mismatch");
+        let conditional: Vec<Distribution1D> = func
+            .chunks(width)
+            .take(height)
+            .map(Distribution1D::new)
+            .collect();
+        let row_integrals: Vec<f32> = conditional.iter().map(|row| row.integral).collect();
+        Self {
+            conditional,
+            marginal: Distribution1D::new(&row_integrals),
+        }
+    }
+
+    /// Map two uniform samples to a position in [0, 1) x [0, 1).
+    ///
+    /// The first coordinate runs along a row, the second over the rows.
+    /// Returns the position and its probability density.
+    pub fn sample_continuous(&self, u: f32, v: f32) -> ((f32, f32), f32) {
+        let (y, pdf_y, row) = self.marginal.sample_continuous(v);
+        let (x, pdf_x, _) = self.conditional[row].sample_continuous(u);
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    /// Probability density at a position in [0, 1) x [0, 1).
+    pub fn pdf(&self, x: f32, y: f32) -> f32 {
+        let row = ((y * self.marginal.count() as f32) as usize).min(self.marginal.count() - 1);
+        let conditional = &self.conditional[row];
+        let column = ((x * conditional.count() as f32) as usize).min(conditional.count() - 1);
+        if self.marginal.integral > 0.0 {
+            conditional.func[column] / self.marginal.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_distribution {
+    use super::*;
+
+    #[test]
+    fn sample_1d_proportional_to_function() {
+        let distribution = Distribution1D::new(&[1.0, 3.0]);
+        assert_relative_eq!(distribution.integral, 2.0);
+        assert_relative_eq!(distribution.pdf(0), 0.5);
+        assert_relative_eq!(distribution.pdf(1), 1.5);
+        // A quarter of the samples end up in the first segment.
+        let (position, pdf, index) = distribution.sample_continuous(0.2);
+        assert_eq!(index, 0);
+        assert_relative_eq!(position, 0.4);
+        assert_relative_eq!(pdf, 0.5);
+        let (position, _, index) = distribution.sample_continuous(0.625);
+        assert_eq!(index, 1);
+        assert_relative_eq!(position, 0.75);
+    }
+
+    #[test]
+    fn sample_1d_skips_empty_segments() {
+        let distribution = Distribution1D::new(&[0.0, 1.0, 0.0]);
+        for i in 0..10 {
+            let (_, _, index) = distribution.sample_continuous(i as f32 / 10.0);
+            assert_eq!(index, 1);
+        }
+    }
+
+    #[test]
+    fn zero_function_is_uniform() {
+        let distribution = Distribution1D::new(&[0.0, 0.0]);
+        let (position, pdf, _) = distribution.sample_continuous(0.3);
+        assert_relative_eq!(position, 0.3);
+        assert_relative_eq!(pdf, 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty distribution")]
+    fn empty_function_panics() {
+        Distribution1D::new(&[]);
+    }
+
+    #[test]
+    fn sample_2d_matches_pdf() {
+        let distribution = Distribution2D::new(&[1.0, 2.0, 3.0, 4.0], 2, 2);
+        assert_relative_eq!(distribution.marginal.integral, 2.5);
+        let ((x, y), pdf) = distribution.sample_continuous(0.9, 0.9);
+        assert!(x >= 0.5 && y >= 0.5);
+        assert_relative_eq!(pdf, 4.0 / 2.5);
+        assert_relative_eq!(distribution.pdf(x, y), pdf);
+    }
+}
@@ -0,0 +1,605 @@
+//! Two dimensional drawing on a canvas.
+//!
+//! Handy for debugging ray paths and for plotting exercises like the
+//! projectile and clock face. Positions are `(x, y)` pairs, where x is the
+//! column and y the row, and pixel `(x, y)` covers the square from `(x, y)`
+//! to `(x + 1, y + 1)`. Everything outside the canvas is clipped, and every
+//! primitive blends its [`Brush`] over the existing pixels.
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+/// Color and opacity to draw with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brush {
+    /// Color
+    pub color: Color<f32>,
+    /// Opacity in [0, 1], 1 replaces pixels
+    pub alpha: f32,
+}
+
+impl Brush {
+    /// Construct an opaque brush.
+    pub fn new(color: Color<f32>) -> Self {
+        Self { color, alpha: 1.0 }
+    }
+
+    /// Set opacity.
+    pub fn with_alpha(mut self, alpha: f32) -> Self {
+        self.alpha = alpha;
+        self
+    }
+}
+
+/// Blend the brush over a pixel, scaled by coverage in [0, 1].
+pub fn plot(canvas: &mut Canvas, x: isize, y: isize, brush: Brush, coverage: f32) {
+    if x < 0 || y < 0 {
+        return;
+    }
+    if let Some(pixel) = canvas.get_mut(y as usize, x as usize) {
+        let alpha = (brush.alpha * coverage).clamp(0.0, 1.0);
+        *pixel = *pixel * (1.0 - alpha) + brush.color * alpha;
+    }
+}
+
+/// Clip a segment to a rectangle with the Liang–Barsky algorithm.
+///
+/// Returns the end points of the part inside, or `None` if there is none.
+fn clip_segment(
+    start: (f64, f64),
+    end: (f64, f64),
+    min: (f64, f64),
+    max: (f64, f64),
+) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    let edges = [
+        (-dx, start.0 - min.0),
+        (dx, max.0 - start.0),
+        (-dy, start.1 - min.1),
+        (dy, max.1 - start.1),
+    ];
+    for &(p, q) in edges.iter() {
+        if p == 0.0 {
+            // Parallel to the edge, either fully inside or outside it.
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    // Rounding can put points of very long segments slightly outside.
+    let point = |t: f64| {
+        (
+            (start.0 + t * dx).clamp(min.0, max.0),
+            (start.1 + t * dy).clamp(min.1, max.1),
+        )
+    };
+    Some((point(t0), point(t1)))
+}
+
+/// Draw an aliased line between two pixels with Bresenham's algorithm.
+///
+/// Parts outside the canvas are clipped before drawing. End points further
+/// away than about 10^15 pixels are clipped with less than pixel precision.
+pub fn line(canvas: &mut Canvas, start: (isize, isize), end: (isize, isize), brush: Brush) {
+    // Clip to a pixel outside the canvas, so that only a few clipped pixels
+    // are walked and the differences below cannot overflow.
+    let clipped = clip_segment(
+        (start.0 as f64, start.1 as f64),
+        (end.0 as f64, end.1 as f64),
+        (-1.0, -1.0),
+        (canvas.width as f64, canvas.height as f64),
+    );
+    let (start, end) = match clipped {
+        Some((start, end)) => (
+            (start.0.round() as isize, start.1.round() as isize),
+            (end.0.round() as isize, end.1.round() as isize),
+        ),
+        None => return,
+    };
+    let (mut x, mut y) = start;
+    let dx = (end.0 - x).abs();
+    let dy = -(end.1 - y).abs();
+    let step_x = if x < end.0 { 1 } else { -1 };
+    let step_y = if y < end.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+    loop {
+        plot(canvas, x, y, brush, 1.0);
+        if (x, y) == end {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Fractional part towards negative infinity, unlike `f32::fract`.
+fn fractional(value: f32) -> f32 {
+    value - value.floor()
+}
+
+/// Draw an anti-aliased line between two points with Xiaolin Wu's algorithm.
+///
+/// Integer coordinates are pixel centers here, like for [`line()`]. Lines with
+/// a non-finite end point are not drawn.
+pub fn line_aa(canvas: &mut Canvas, start: (f32, f32), end: (f32, f32), brush: Brush) {
+    let finite = |(x, y): (f32, f32)| x.is_finite() && y.is_finite();
+    if !finite(start) || !finite(end) {
+        return;
+    }
+    // Clip with a margin, so that end points cut off by the clipping lie
+    // outside the canvas and their partial coverage is not visible.
+    let clipped = clip_segment(
+        (start.0 as f64, start.1 as f64),
+        (end.0 as f64, end.1 as f64),
+        (-2.0, -2.0),
+        (canvas.width as f64 + 1.0, canvas.height as f64 + 1.0),
+    );
+    let ((x0, y0), (x1, y1)) = match clipped {
+        Some(segment) => segment,
+        None => return,
+    };
+    let (mut x0, mut y0, mut x1, mut y1) = (x0 as f32, y0 as f32, x1 as f32, y1 as f32);
+    let steep = (y1 - y0).abs() > (x1 - x0).abs();
+    if steep {
+        std::mem::swap(&mut x0, &mut y0);
+        std::mem::swap(&mut x1, &mut y1);
+    }
+    if x0 > x1 {
+        std::mem::swap(&mut x0, &mut x1);
+        std::mem::swap(&mut y0, &mut y1);
+    }
+    let mut draw = |major: f32, minor: f32, coverage: f32| {
+        let (x, y) = if steep {
+            (minor, major)
+        } else {
+            (major, minor)
+        };
+        plot(canvas, x as isize, y as isize, brush, coverage);
+    };
+    let gradient = if x1 == x0 { 1.0 } else { (y1 - y0) / (x1 - x0) };
+
+    // End points cover part of their pixel along the major axis.
+    let mut end_point = |x: f32, y: f32, first: bool| {
+        let x_end = x.round();
+        let y_end = y + gradient * (x_end - x);
+        let gap = if first {
+            1.0 - fractional(x + 0.5)
+        } else {
+            fractional(x + 0.5)
+        };
+        let y_floor = y_end.floor();
+        draw(x_end, y_floor, (1.0 - fractional(y_end)) * gap);
+        draw(x_end, y_floor + 1.0, fractional(y_end) * gap);
+        (x_end, y_end)
+    };
+    let (x_start, y_start) = end_point(x0, y0, true);
+    let (x_stop, _) = end_point(x1, y1, false);
+
+    for step in 1..(x_stop - x_start) as isize {
+        let x = x_start + step as f32;
+        let y = y_start + gradient * step as f32;
+        let y_floor = y.floor();
+        draw(x, y_floor, 1.0 - fractional(y));
+        draw(x, y_floor + 1.0, fractional(y));
+    }
+}
+
+/// Draw the outline of a circle with the midpoint algorithm.
+pub fn circle(canvas: &mut Canvas, center: (isize, isize), radius: isize, brush: Brush) {
+    let (cx, cy) = center;
+    let (mut x, mut y) = (radius, 0);
+    let mut error = 1 - radius;
+    while x >= y {
+        // Plot the eight symmetric points, skipping duplicates on the diagonals
+        // and axes so that translucent brushes blend every pixel once.
+        let mut points = vec![
+            (x, y),
+            (y, x),
+            (-y, x),
+            (-x, y),
+            (-x, -y),
+            (-y, -x),
+            (y, -x),
+            (x, -y),
+        ];
+        points.sort_unstable();
+        points.dedup();
+        for (dx, dy) in points {
+            plot(canvas, cx + dx, cy + dy, brush, 1.0);
+        }
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+}
+
+/// Draw a filled circle.
+pub fn fill_circle(canvas: &mut Canvas, center: (isize, isize), radius: isize, brush: Brush) {
+    let (cx, cy) = center;
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            // Adding the radius rounds the edge like the midpoint outline.
+            if dx * dx + dy * dy <= radius * radius + radius {
+                plot(canvas, cx + dx, cy + dy, brush, 1.0);
+            }
+        }
+    }
+}
+
+/// Draw the outline of a rectangle with its top left pixel at `corner`.
+pub fn rectangle(canvas: &mut Canvas, corner: (isize, isize), size: (usize, usize), brush: Brush) {
+    let (x, y) = corner;
+    let (width, height) = (size.0 as isize, size.1 as isize);
+    if width == 0 || height == 0 {
+        return;
+    }
+    for dx in 0..width {
+        plot(canvas, x + dx, y, brush, 1.0);
+        if height > 1 {
+            plot(canvas, x + dx, y + height - 1, brush, 1.0);
+        }
+    }
+    for dy in 1..height - 1 {
+        plot(canvas, x, y + dy, brush, 1.0);
+        if width > 1 {
+            plot(canvas, x + width - 1, y + dy, brush, 1.0);
+        }
+    }
+}
+
+/// Draw a filled rectangle with its top left pixel at `corner`.
+pub fn fill_rectangle(
+    canvas: &mut Canvas,
+    corner: (isize, isize),
+    size: (usize, usize),
+    brush: Brush,
+) {
+    let (x, y) = corner;
+    for dy in 0..size.1 as isize {
+        for dx in 0..size.0 as isize {
+            plot(canvas, x + dx, y + dy, brush, 1.0);
+        }
+    }
+}
+
+/// Fill a polygon with a scanline fill, using the even-odd rule.
+///
+/// A pixel is filled when its center lies inside the polygon. Points with a
+/// non-finite coordinate are skipped.
+pub fn fill_polygon(canvas: &mut Canvas, points: &[(f32, f32)], brush: Brush) {
+    let points: Vec<(f32, f32)> = points
+        .iter()
+        .copied()
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .collect();
+    if points.len() < 3 {
+        return;
+    }
+    let mut crossings = vec![];
+    for row in 0..canvas.height {
+        let y = row as f32 + 0.5;
+        crossings.clear();
+        for (i, &(x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(i + 1) % points.len()];
+            // Half open test, so a vertex on the scanline counts once.
+            if (y0 <= y) != (y1 <= y) {
+                crossings.push(x0 + (y - y0) / (y1 - y0) * (x1 - x0));
+            }
+        }
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        for span in crossings.chunks(2) {
+            if let [start, end] = *span {
+                let first = ((start - 0.5).ceil() as isize).max(0);
+                let last = ((end - 0.5).ceil() as isize).min(canvas.width as isize);
+                for x in first..last {
+                    plot(canvas, x, row as isize, brush, 1.0);
+                }
+            }
+        }
+    }
+}
+
+/// Width of a glyph of the built-in font, in pixels.
+pub const GLYPH_WIDTH: usize = 3;
+
+/// Height of a glyph of the built-in font, in pixels.
+pub const GLYPH_HEIGHT: usize = 5;
+
+/// Return the 3x5 bitmap of a character, row by row with three bits each.
+///
+/// Lowercase letters use the uppercase glyphs, and unknown characters
+/// become a question mark.
+fn glyph(character: char) -> u16 {
+    match character.to_ascii_uppercase() {
+        '0' => 0b111_101_101_101_111,
+        '1' => 0b010_110_010_010_111,
+        '2' => 0b111_001_111_100_111,
+        '3' => 0b111_001_111_001_111,
+        '4' => 0b101_101_111_001_001,
+        '5' => 0b111_100_111_001_111,
+        '6' => 0b111_100_111_101_111,
+        '7' => 0b111_001_001_001_001,
+        '8' => 0b111_101_111_101_111,
+        '9' => 0b111_101_111_001_111,
+        'A' => 0b010_101_111_101_101,
+        'B' => 0b110_101_110_101_110,
+        'C' => 0b011_100_100_100_011,
+        'D' => 0b110_101_101_101_110,
+        'E' => 0b111_100_110_100_111,
+        'F' => 0b111_100_110_100_100,
+        'G' => 0b011_100_101_101_011,
+        'H' => 0b101_101_111_101_101,
+        'I' => 0b111_010_010_010_111,
+        'J' => 0b001_001_001_101_010,
+        'K' => 0b101_101_110_101_101,
+        'L' => 0b100_100_100_100_111,
+        'M' => 0b101_111_111_101_101,
+        'N' => 0b110_101_101_101_101,
+        'O' => 0b010_101_101_101_010,
+        'P' => 0b110_101_110_100_100,
+        'Q' => 0b010_101_101_110_011,
+        'R' => 0b110_101_110_101_101,
+        'S' => 0b011_100_010_001_110,
+        'T' => 0b111_010_010_010_010,
+        'U' => 0b101_101_101_101_111,
+        'V' => 0b101_101_101_101_010,
+        'W' => 0b101_101_111_111_101,
+        'X' => 0b101_101_010_101_101,
+        'Y' => 0b101_101_010_010_010,
+        'Z' => 0b111_001_010_100_111,
+        ' ' => 0,
+        '.' => 0b000_000_000_000_010,
+        ',' => 0b000_000_000_010_100,
+        ':' => 0b000_010_000_010_000,
+        '-' => 0b000_000_111_000_000,
+        '+' => 0b000_010_111_010_000,
+        '=' => 0b000_111_000_111_000,
+        '/' => 0b001_001_010_100_100,
+        '(' => 0b010_100_100_100_010,
+        ')' => 0b010_001_001_001_010,
+        _ => 0b111_001_010_000_010,
+    }
+}
+
+/// Draw text with the built-in 3x5 pixel font, with its top left at `corner`.
+///
+/// Every font pixel becomes a `scale` by `scale` square. Characters are one
+/// font pixel apart and lines two, and `\n` starts a new line.
+pub fn text(canvas: &mut Canvas, corner: (isize, isize), text: &str, scale: usize, brush: Brush) {
+    let scale = scale as isize;
+    let advance = (GLYPH_WIDTH as isize + 1) * scale;
+    let line_height = (GLYPH_HEIGHT as isize + 2) * scale;
+    let (mut x, mut y) = corner;
+    for character in text.chars() {
+        if character == '\n' {
+            x = corner.0;
+            y += line_height;
+            continue;
+        }
+        let bits = glyph(character);
+        for row in 0..GLYPH_HEIGHT {
+            for column in 0..GLYPH_WIDTH {
+                let bit = (GLYPH_HEIGHT - 1 - row) * GLYPH_WIDTH + (GLYPH_WIDTH - 1 - column);
+                if bits & (1 << bit) != 0 {
+                    let corner = (x + column as isize * scale, y + row as isize * scale);
+                    fill_rectangle(canvas, corner, (scale as usize, scale as usize), brush);
+                }
+            }
+        }
+        x += advance;
+    }
+}
+
+#[cfg(test)]
+mod test_draw {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+
+    use super::*;
+
+    fn white() -> Brush {
+        Brush::new(Color::new(1.0, 1.0, 1.0))
+    }
+
+    /// Return the canvas as rows of `#` for lit and `.` for dark pixels.
+    fn picture(canvas: &Canvas) -> Vec<String> {
+        canvas
+            .pixels
+            .chunks(canvas.width)
+            .map(|row| {
+                row.iter()
+                    .map(|pixel| if pixel.r > 0.5 { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plot_blends_and_clips() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.fill(Color::new(0.0, 0.0, 1.0));
+        let brush = Brush::new(Color::new(1.0, 0.0, 0.0)).with_alpha(0.5);
+        plot(&mut canvas, 1, 0, brush, 1.0);
+        plot(&mut canvas, -1, 0, brush, 1.0);
+        plot(&mut canvas, 2, 0, brush, 1.0);
+        assert_relative_eq!(canvas[(0, 1)], Color::new(0.5, 0.0, 0.5));
+        plot(&mut canvas, 0, 0, brush, 0.5);
+        assert_relative_eq!(canvas[(0, 0)], Color::new(0.25, 0.0, 0.75));
+    }
+
+    #[test]
+    fn bresenham_lines() {
+        let mut canvas = Canvas::new(3, 5);
+        line(&mut canvas, (0, 0), (4, 2), white());
+        assert!(picture(&canvas) == vec!["#....", ".##..", "...##"]);
+        let mut reversed = Canvas::new(3, 5);
+        line(&mut reversed, (4, 2), (0, 0), white());
+        let lit = reversed.pixels.iter().filter(|pixel| pixel.r > 0.5).count();
+        assert!(lit == 5);
+        // Partly outside the canvas.
+        let mut clipped = Canvas::new(2, 2);
+        line(&mut clipped, (-2, 1), (5, 1), white());
+        assert!(picture(&clipped) == vec!["..", "##"]);
+        // Far away end points are clipped instead of walked, and extreme
+        // ones do not overflow.
+        let mut far = Canvas::new(3, 3);
+        line(
+            &mut far,
+            (-1_000_000_000, -1_000_000_000),
+            (1_000_000_000, 1_000_000_000),
+            white(),
+        );
+        assert!(picture(&far) == vec!["#..", ".#.", "..#"]);
+        let mut extreme = Canvas::new(3, 3);
+        line(&mut extreme, (isize::MIN, 1), (isize::MAX, 1), white());
+        let rows = picture(&extreme);
+        assert!(rows[0] == "..." && rows[1].contains('#') && rows[2] == "...");
+        let mut missed = Canvas::new(2, 2);
+        line(&mut missed, (-5, 3), (5, 3), white());
+        assert!(picture(&missed) == vec!["..", ".."]);
+    }
+
+    #[test]
+    fn wu_lines() {
+        let mut canvas = Canvas::new(3, 5);
+        line_aa(&mut canvas, (0.0, 1.0), (4.0, 1.0), white());
+        assert!(picture(&canvas) == vec![".....", ".###.", "....."]);
+        assert_relative_eq!(canvas[(1, 2)], Color::new(1.0, 1.0, 1.0));
+        // The line ends at the end pixel centers, covering half of them.
+        assert_relative_eq!(canvas[(1, 0)].r, 0.5);
+        assert_relative_eq!(canvas[(1, 4)].r, 0.5);
+
+        // A line between pixel rows is shared by both rows.
+        let mut canvas = Canvas::new(3, 5);
+        line_aa(&mut canvas, (0.0, 0.5), (4.0, 0.5), white());
+        assert_relative_eq!(canvas[(0, 2)].r, 0.5);
+        assert_relative_eq!(canvas[(1, 2)].r, 0.5);
+
+        // Steep diagonal, total coverage per row is one pixel.
+        let mut canvas = Canvas::new(5, 5);
+        line_aa(&mut canvas, (1.0, 0.0), (2.0, 4.0), white());
+        for row in 1..4 {
+            let total: f32 = (0..5).map(|column| canvas[(row, column)].r).sum();
+            assert_relative_eq!(total, 1.0, epsilon = 1e-5);
+        }
+
+        // Starting outside the canvas at negative coordinates.
+        let mut canvas = Canvas::new(2, 5);
+        line_aa(&mut canvas, (-2.0, -0.25), (4.0, -0.25), white());
+        for column in 0..4 {
+            assert_relative_eq!(canvas[(0, column)].r, 0.75);
+        }
+        assert_relative_eq!(canvas[(0, 4)].r, 0.375);
+        assert_relative_eq!(canvas[(1, 2)].r, 0.0);
+
+        // Far away and non-finite end points neither hang nor draw garbage.
+        let mut canvas = Canvas::new(3, 4);
+        line_aa(&mut canvas, (-3.0e7, 1.0), (3.0e7, 1.0), white());
+        assert!(picture(&canvas) == vec!["....", "####", "...."]);
+        let mut canvas = Canvas::new(4, 4);
+        line_aa(&mut canvas, (0.0, 0.0), (3.0e7, 1.0), white());
+        line_aa(&mut canvas, (0.0, 0.0), (f32::INFINITY, 1.0), white());
+        line_aa(&mut canvas, (f32::NAN, 0.0), (2.0, 2.0), white());
+        // The start pixel center is covered by half, like end points above.
+        assert!(picture(&canvas) == vec![".###", "....", "....", "...."]);
+        assert_relative_eq!(canvas[(0, 0)].r, 0.5);
+    }
+
+    #[test]
+    fn circles() {
+        let mut canvas = Canvas::new(7, 7);
+        circle(&mut canvas, (3, 3), 3, white());
+        assert!(
+            picture(&canvas)
+                == vec![
+                    "..###..", ".#...#.", "#.....#", "#.....#", "#.....#", ".#...#.", "..###..",
+                ]
+        );
+        let mut canvas = Canvas::new(5, 5);
+        fill_circle(&mut canvas, (2, 2), 2, white());
+        assert!(picture(&canvas) == vec![".###.", "#####", "#####", "#####", ".###."]);
+    }
+
+    #[test]
+    fn translucent_circle_blends_once() {
+        let mut canvas = Canvas::new(9, 9);
+        circle(&mut canvas, (4, 4), 4, white().with_alpha(0.5));
+        assert!(canvas
+            .pixels
+            .iter()
+            .all(|pixel| pixel.r == 0.0 || pixel.r == 0.5));
+    }
+
+    #[test]
+    fn rectangles() {
+        let mut canvas = Canvas::new(4, 5);
+        rectangle(&mut canvas, (1, 0), (4, 3), white());
+        assert!(picture(&canvas) == vec![".####", ".#..#", ".####", "....."]);
+        let mut canvas = Canvas::new(3, 3);
+        fill_rectangle(&mut canvas, (1, 1), (5, 5), white());
+        assert!(picture(&canvas) == vec!["...", ".##", ".##"]);
+    }
+
+    #[test]
+    fn polygon_fill() {
+        let mut canvas = Canvas::new(4, 4);
+        fill_polygon(&mut canvas, &[(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)], white());
+        assert!(picture(&canvas) == vec!["###.", "##..", "#...", "...."]);
+
+        // Even-odd rule leaves the hole of a ring empty.
+        let mut canvas = Canvas::new(5, 5);
+        let square = [(1.0, 1.0), (4.0, 1.0), (4.0, 4.0), (1.0, 4.0)];
+        let ring = [
+            (0.0, 0.0),
+            (5.0, 0.0),
+            (5.0, 5.0),
+            (0.0, 5.0),
+            (0.0, 0.0),
+            square[0],
+            square[3],
+            square[2],
+            square[1],
+            square[0],
+        ];
+        fill_polygon(&mut canvas, &ring, white());
+        assert!(picture(&canvas) == vec!["#####", "#...#", "#...#", "#...#", "#####"]);
+
+        // Non-finite points are skipped.
+        let mut canvas = Canvas::new(4, 4);
+        let points = [(0.0, 0.0), (f32::NAN, 2.0), (4.0, 0.0), (0.0, 4.0)];
+        fill_polygon(&mut canvas, &points, white());
+        assert!(picture(&canvas) == vec!["###.", "##..", "#...", "...."]);
+    }
+
+    #[test]
+    fn bitmap_text() {
+        let mut canvas = Canvas::new(5, 7);
+        text(&mut canvas, (0, 0), "1l", 1, white());
+        assert!(picture(&canvas) == vec![".#..#..", "##..#..", ".#..#..", ".#..#..", "###.###"]);
+        let mut canvas = Canvas::new(20, 8);
+        text(&mut canvas, (1, 0), "-\n-", 2, white());
+        assert!(picture(&canvas)[4] == ".######.");
+        assert!(picture(&canvas)[5] == ".######.");
+        assert!(picture(&canvas)[18] == ".######.");
+        assert!(picture(&canvas)[17] == "........");
+    }
+}
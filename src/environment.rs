@@ -0,0 +1,357 @@
+//! Environment lighting for rays that escape the scene.
+//!
+//! An environment map returns the radiance arriving from a direction, read
+//! from either a latitude-longitude (equirectangular) image or six cube map
+//! faces. Latitude-longitude maps can be importance sampled, so that bright
+//! regions such as the sun in an HDRI are found with few samples.
+
+use std::f32::consts::PI;
+
+use num::traits::Float;
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::distribution::Distribution2D;
+use crate::texture::{ImageTexture, WrapMode};
+use crate::uv::{cube_map, spherical_map, CubeFace};
+use crate::vectors::Vec3;
+
+/// Images an environment map reads from.
+pub enum EnvironmentSource {
+    /// Single image covering all directions, longitude along u.
+    LatLong(ImageTexture),
+    /// Six square images, one per cube face.
+    CubeMap(Box<CubeFaces>),
+}
+
+/// One texture per face of a cube map.
+pub struct CubeFaces {
+    /// Face towards -x
+    pub left: ImageTexture,
+    /// Face towards +x
+    pub right: ImageTexture,
+    /// Face towards +z
+    pub front: ImageTexture,
+    /// Face towards -z
+    pub back: ImageTexture,
+    /// Face towards +y
+    pub up: ImageTexture,
+    /// Face towards -y
+    pub down: ImageTexture,
+}
+
+/// Environment surrounding the scene at infinite distance.
+pub struct EnvironmentMap {
+    /// Image data
+    pub source: EnvironmentSource,
+    /// Rotation around the y-axis in radians
+    pub rotation: f32,
+    /// Factor applied to all looked up colors
+    pub intensity: f32,
+}
+
+impl EnvironmentMap {
+    /// Construct a new environment map from a latitude-longitude canvas.
+    ///
+    /// Lookups wrap around horizontally but not across the poles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the canvas is empty.
+    pub fn lat_long(canvas: Canvas) -> Self {
+        let texture = ImageTexture::new(canvas).with_wrap_uv(WrapMode::Repeat, WrapMode::Clamp);
+        Self {
+            source: EnvironmentSource::LatLong(texture),
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    /// Construct a new environment map from six cube faces.
+    ///
+    /// Faces are given in the order left, right, front, back, up, down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a face is empty.
+    pub fn cube_map(faces: [Canvas; 6]) -> Self {
+        let [left, right, front, back, up, down] =
+            faces.map(|face| ImageTexture::new(face).with_wrap(WrapMode::Clamp));
+        Self {
+            source: EnvironmentSource::CubeMap(Box::new(CubeFaces {
+                left,
+                right,
+                front,
+                back,
+                up,
+                down,
+            })),
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    /// Return environment map with the given rotation around the y-axis.
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Return environment map with the given intensity.
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Return radiance arriving from a direction.
+    pub fn color_at<T: Float>(&self, direction: &Vec3<T>) -> Color<f32> {
+        let direction = rotate_y(&to_f32(direction), -self.rotation);
+        let color = match &self.source {
+            EnvironmentSource::LatLong(texture) => {
+                let (u, v) = spherical_map(&direction);
+                texture.color_at(u, v)
+            }
+            EnvironmentSource::CubeMap(faces) => {
+                // Project onto the unit cube.
+                let scale = direction
+                    .x
+                    .abs()
+                    .max(direction.y.abs())
+                    .max(direction.z.abs());
+                let (face, u, v) = cube_map(&(direction / scale));
+                let texture = match face {
+                    CubeFace::Left => &faces.left,
+                    CubeFace::Right => &faces.right,
+                    CubeFace::Front => &faces.front,
+                    CubeFace::Back => &faces.back,
+                    CubeFace::Up => &faces.up,
+                    CubeFace::Down => &faces.down,
+                };
+                texture.color_at(u, v)
+            }
+        };
+        color * self.intensity
+    }
+}
+
+/// Importance sampler for a latitude-longitude environment map.
+pub struct EnvironmentSampler {
+    distribution: Distribution2D,
+    rotation: f32,
+}
+
+/// Direction sampled from an environment map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvironmentSample {
+    /// Normalized direction towards the environment
+    pub direction: Vec3<f32>,
+    /// Probability density with respect to solid angle
+    pub pdf: f32,
+}
+
+impl EnvironmentSampler {
+    /// Build a sampler for the environment map.
+    ///
+    /// Returns `None` for cube maps, which are not supported.
+    pub fn new(environment: &EnvironmentMap) -> Option<Self> {
+        let canvas = match &environment.source {
+            EnvironmentSource::LatLong(texture) => &texture.canvas,
+            EnvironmentSource::CubeMap(_) => return None,
+        };
+        let (width, height) = (canvas.width, canvas.height);
+        let mut weights = Vec::with_capacity(width * height);
+        for row in 0..height {
+            // Rows near the poles cover less solid angle.
+            let sin_phi = ((row as f32 + 0.5) / height as f32 * PI).sin();
+            for column in 0..width {
+                weights.push(canvas[(row, column)].luminance() * sin_phi);
+            }
+        }
+        Some(Self {
+            distribution: Distribution2D::new(&weights, width, height),
+            rotation: environment.rotation,
+        })
+    }
+
+    /// Map two uniform samples in [0, 1) to a direction.
+    pub fn sample(&self, u1: f32, u2: f32) -> EnvironmentSample {
+        // x runs along image columns, y down the rows.
+        let ((x, y), pdf_image) = self.distribution.sample_continuous(u1, u2);
+        let (u, v) = (x, 1.0 - y);
+        let theta = (0.5 - u) * 2.0 * PI;
+        let phi = (1.0 - v) * PI;
+        let local = Vec3 {
+            x: phi.sin() * theta.sin(),
+            y: phi.cos(),
+            z: phi.sin() * theta.cos(),
+        };
+        let sin_phi = phi.sin();
+        let pdf = if sin_phi > 0.0 {
+            pdf_image / (2.0 * PI * PI * sin_phi)
+        } else {
+            0.0
+        };
+        EnvironmentSample {
+            direction: rotate_y(&local, self.rotation),
+            pdf,
+        }
+    }
+
+    /// Probability density of sampling a direction, with respect to solid angle.
+    pub fn pdf<T: Float>(&self, direction: &Vec3<T>) -> f32 {
+        let direction = rotate_y(&to_f32(direction), -self.rotation).normalize();
+        let (u, v) = spherical_map(&direction);
+        let sin_phi = (1.0 - direction.y * direction.y).max(0.0).sqrt();
+        if sin_phi <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, 1.0 - v) / (2.0 * PI * PI * sin_phi)
+    }
+}
+
+fn to_f32<T: Float>(vector: &Vec3<T>) -> Vec3<f32> {
+    Vec3 {
+        x: vector.x.to_f32().unwrap(),
+        y: vector.y.to_f32().unwrap(),
+        z: vector.z.to_f32().unwrap(),
+    }
+}
+
+fn rotate_y(vector: &Vec3<f32>, angle: f32) -> Vec3<f32> {
+    let (sin, cos) = angle.sin_cos();
+    Vec3 {
+        x: cos * vector.x + sin * vector.z,
+        y: vector.y,
+        z: -sin * vector.x + cos * vector.z,
+    }
+}
+
+#[cfg(test)]
+mod test_environment {
+    use std::f32::consts::PI;
+
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::vectors::Vec3;
+
+    use super::*;
+
+    fn vector(x: f32, y: f32, z: f32) -> Vec3<f32> {
+        Vec3 { x, y, z }
+    }
+
+    /// Latitude-longitude map that is black except for one bright texel.
+    fn bright_spot_map() -> EnvironmentMap {
+        let mut canvas = Canvas::new(8, 16);
+        canvas.fill(Color::new(0.01, 0.01, 0.01));
+        canvas[(3, 4)] = Color::new(100.0, 100.0, 100.0);
+        EnvironmentMap::lat_long(canvas)
+    }
+
+    #[test]
+    fn lat_long_lookup_with_intensity() {
+        let mut canvas = Canvas::new(2, 4);
+        canvas.fill(Color::new(0.5, 0.25, 1.0));
+        let environment = EnvironmentMap::lat_long(canvas).with_intensity(2.0);
+        let result = environment.color_at(&vector(0.3, 0.2, -0.9));
+        assert_relative_eq!(result, Color::new(1.0, 0.5, 2.0));
+    }
+
+    #[test]
+    fn zenith_does_not_wrap_to_ground() {
+        let mut canvas = Canvas::new(4, 8);
+        for row in 0..4 {
+            for column in 0..8 {
+                canvas[(row, column)] = if row < 2 {
+                    Color::new(0.2, 0.4, 1.0)
+                } else {
+                    Color::new(0.3, 0.2, 0.1)
+                };
+            }
+        }
+        let environment = EnvironmentMap::lat_long(canvas);
+        assert_relative_eq!(
+            environment.color_at(&vector(0.0, 1.0, 0.0)),
+            Color::new(0.2, 0.4, 1.0)
+        );
+        assert_relative_eq!(
+            environment.color_at(&vector(0.0, -1.0, 0.0)),
+            Color::new(0.3, 0.2, 0.1)
+        );
+    }
+
+    #[test]
+    fn rotation_turns_environment() {
+        let stripes = || {
+            let mut canvas = Canvas::new(1, 4);
+            for column in 0..4 {
+                canvas[(0, column)] = Color::new(column as f32, 0.0, 0.0);
+            }
+            canvas
+        };
+        let unrotated = EnvironmentMap::lat_long(stripes());
+        let rotated = EnvironmentMap::lat_long(stripes()).with_rotation(PI / 2.0);
+        // Turning the map a quarter brings what was along +z to +x.
+        assert_relative_eq!(
+            rotated.color_at(&vector(1.0, 0.0, 0.0)),
+            unrotated.color_at(&vector(0.0, 0.0, 1.0)),
+            epsilon = 1e-5
+        );
+    }
+
+    #[test]
+    fn cube_map_lookup_picks_face() {
+        let faces = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0].map(|value| {
+            let mut canvas = Canvas::new(2, 2);
+            canvas.fill(Color::new(value, value, value));
+            canvas
+        });
+        let environment = EnvironmentMap::cube_map(faces);
+        assert_relative_eq!(environment.color_at(&vector(-2.0, 0.1, 0.3)).r, 0.0);
+        assert_relative_eq!(environment.color_at(&vector(1.0, 0.1, 0.3)).r, 1.0);
+        assert_relative_eq!(environment.color_at(&vector(0.1, 0.3, 5.0)).r, 2.0);
+        assert_relative_eq!(environment.color_at(&vector(0.1, 0.3, -5.0)).r, 3.0);
+        assert_relative_eq!(environment.color_at(&vector(0.1, 0.3, 0.2)).r, 4.0);
+        assert_relative_eq!(environment.color_at(&vector(0.1, -0.3, 0.2)).r, 5.0);
+    }
+
+    #[test]
+    fn sampler_prefers_bright_texel() {
+        let environment = bright_spot_map();
+        let sampler = EnvironmentSampler::new(&environment).unwrap();
+        let bright = environment.color_at(&sampler.sample(0.5, 0.5).direction);
+        assert!(bright.r > 1.0);
+        let sample = sampler.sample(0.123, 0.77);
+        assert_relative_eq!(sample.direction.magnitude(), 1.0, epsilon = 1e-6);
+        assert_relative_eq!(
+            sampler.pdf(&sample.direction),
+            sample.pdf,
+            max_relative = 1e-3
+        );
+    }
+
+    #[test]
+    fn sampler_pdf_integrates_to_one() {
+        let environment = bright_spot_map().with_rotation(0.7);
+        let sampler = EnvironmentSampler::new(&environment).unwrap();
+        // Integrate the pdf over the sphere on a fine latitude-longitude grid.
+        let (n_theta, n_phi) = (400, 200);
+        let mut integral = 0.0;
+        for i in 0..n_phi {
+            let phi = (i as f32 + 0.5) / n_phi as f32 * PI;
+            for j in 0..n_theta {
+                let theta = (j as f32 + 0.5) / n_theta as f32 * 2.0 * PI;
+                let direction = vector(phi.sin() * theta.sin(), phi.cos(), phi.sin() * theta.cos());
+                let solid_angle = phi.sin() * (PI / n_phi as f32) * (2.0 * PI / n_theta as f32);
+                integral += sampler.pdf(&direction) * solid_angle;
+            }
+        }
+        assert_relative_eq!(integral, 1.0, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn cube_maps_are_not_sampled() {
+        let faces = [(); 6].map(|_| Canvas::new(1, 1));
+        assert!(EnvironmentSampler::new(&EnvironmentMap::cube_map(faces)).is_none());
+    }
+}
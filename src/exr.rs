@@ -0,0 +1,204 @@
+//! For saving canvases to OpenEXR .exr file.
+//!
+//! Writes single part scanline images with uncompressed 32-bit float
+//! channels. Like other EXR images, colors are linear and premultiplied by
+//! alpha. Channels can be grouped in layers by giving them names like
+//! `normal.X`, which compositing applications show as separate passes.
+
+use crate::rgba::{Rgba, RgbaCanvas};
+use crate::tonemap::ToneMapping;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+/// Pixel type of 32-bit float channels.
+const FLOAT: i32 = 2;
+
+/// Named channel of an EXR image, with one value per pixel in row order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExrChannel {
+    /// Channel name, optionally prefixed by a layer name and a dot
+    pub name: String,
+    /// Values
+    pub values: Vec<f32>,
+}
+
+impl ExrChannel {
+    /// Convenience function for creating a new channel.
+    pub fn new(name: &str, values: Vec<f32>) -> Self {
+        Self {
+            name: name.to_string(),
+            values,
+        }
+    }
+}
+
+/// Reads canvas pixels and writes to EXR bytes with R, G, B and A channels.
+pub fn exr_from_canvas(canvas: &RgbaCanvas) -> Vec<u8> {
+    exr_from_canvas_with(canvas, &ToneMapping::default())
+}
+
+/// Reads canvas pixels, tone maps them and writes to EXR bytes.
+///
+/// Values stay linear and premultiplied, as EXR images are never display
+/// encoded.
+pub fn exr_from_canvas_with(canvas: &RgbaCanvas, tone_mapping: &ToneMapping) -> Vec<u8> {
+    let pixels: Vec<Rgba> = canvas
+        .pixels
+        .iter()
+        .map(|pixel| pixel.tone_mapped(tone_mapping))
+        .collect();
+    let channel = |name: &str, value: fn(&Rgba) -> f32| {
+        ExrChannel::new(name, pixels.iter().map(value).collect())
+    };
+    exr_from_channels(
+        canvas.height,
+        canvas.width,
+        vec![
+            channel("R", |pixel| pixel.color.r),
+            channel("G", |pixel| pixel.color.g),
+            channel("B", |pixel| pixel.color.b),
+            channel("A", |pixel| pixel.alpha),
+        ],
+    )
+}
+
+/// Write channels of the given size to EXR bytes.
+///
+/// # Panics
+///
+/// Panics if a channel does not have one value per pixel.
+pub fn exr_from_channels(height: usize, width: usize, mut channels: Vec<ExrChannel>) -> Vec<u8> {
+    // EXR requires channels in alphabetical order.
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    for channel in channels.iter() {
+        assert_eq!(
+            channel.values.len(),
+            height * width,
+            "channel {} has wrong size",
+            channel.name
+        );
+    }
+
+    let mut bytes = MAGIC.to_vec();
+    // Version 2, single part scanline file.
+    bytes.extend_from_slice(&2_i32.to_le_bytes());
+
+    let mut list = vec![];
+    for channel in channels.iter() {
+        list.extend_from_slice(channel.name.as_bytes());
+        list.push(0);
+        list.extend_from_slice(&FLOAT.to_le_bytes());
+        // Not perceptually linear, three reserved bytes, no subsampling.
+        list.extend_from_slice(&[0, 0, 0, 0]);
+        list.extend_from_slice(&1_i32.to_le_bytes());
+        list.extend_from_slice(&1_i32.to_le_bytes());
+    }
+    list.push(0);
+    write_attribute(&mut bytes, "channels", "chlist", &list);
+    write_attribute(&mut bytes, "compression", "compression", &[0]);
+    let mut window = vec![];
+    for value in [0, 0, width as i32 - 1, height as i32 - 1].iter() {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    write_attribute(&mut bytes, "dataWindow", "box2i", &window);
+    write_attribute(&mut bytes, "displayWindow", "box2i", &window);
+    write_attribute(&mut bytes, "lineOrder", "lineOrder", &[0]);
+    write_attribute(
+        &mut bytes,
+        "pixelAspectRatio",
+        "float",
+        &1.0_f32.to_le_bytes(),
+    );
+    write_attribute(&mut bytes, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(
+        &mut bytes,
+        "screenWindowWidth",
+        "float",
+        &1.0_f32.to_le_bytes(),
+    );
+    bytes.push(0);
+
+    // Offset table with the position of every scanline, then the scanlines.
+    let line_size = 8 + width * channels.len() * 4;
+    let first_line = bytes.len() + height * 8;
+    for y in 0..height {
+        bytes.extend_from_slice(&((first_line + y * line_size) as u64).to_le_bytes());
+    }
+    for y in 0..height {
+        bytes.extend_from_slice(&(y as i32).to_le_bytes());
+        bytes.extend_from_slice(&((line_size - 8) as i32).to_le_bytes());
+        for channel in channels.iter() {
+            for value in channel.values[y * width..(y + 1) * width].iter() {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+fn write_attribute(bytes: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(kind.as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod test_exr {
+    use crate::color::Color;
+    use crate::rgba::{Rgba, RgbaCanvas};
+
+    use super::*;
+
+    fn read_f32(bytes: &[u8], position: usize) -> f32 {
+        let mut value = [0; 4];
+        value.copy_from_slice(&bytes[position..position + 4]);
+        f32::from_le_bytes(value)
+    }
+
+    #[test]
+    fn test_exr_from_canvas() {
+        let mut canvas = RgbaCanvas::new(2, 3);
+        canvas[(1, 2)] = Rgba::from_straight(Color::new(4.0, 0.5, 0.25), 0.5);
+        let exr = exr_from_canvas(&canvas);
+        assert!(exr[..8] == [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        assert!(exr[8..28] == *b"channels\0chlist\0\x49\0\0\0");
+
+        let line_size = 8 + 3 * 4 * 4;
+        let second_line = exr.len() - line_size;
+        let header_end = exr.len() - 2 * line_size - 2 * 8;
+        assert!(exr[header_end - 1] == 0);
+        assert!(exr[header_end + 8..header_end + 16] == (second_line as u64).to_le_bytes());
+        assert!(exr[second_line..second_line + 8] == [1, 0, 0, 0, 48, 0, 0, 0]);
+
+        // The last pixel of every channel in the second scanline.
+        let channel = |index: usize| read_f32(&exr, second_line + 8 + index * 12 + 8);
+        assert_relative_eq!(channel(0), 0.5);
+        assert_relative_eq!(channel(1), 0.125);
+        assert_relative_eq!(channel(2), 0.25);
+        assert_relative_eq!(channel(3), 2.0);
+    }
+
+    #[test]
+    fn test_exr_channels_are_sorted() {
+        let exr = exr_from_channels(
+            1,
+            1,
+            vec![
+                ExrChannel::new("normal.Z", vec![3.0]),
+                ExrChannel::new("depth.Z", vec![1.0]),
+                ExrChannel::new("normal.X", vec![2.0]),
+            ],
+        );
+        let list = &exr[28..];
+        assert!(list.starts_with(b"depth.Z\0"));
+        assert!(list[24..].starts_with(b"normal.X\0"));
+        assert!(list[49..].starts_with(b"normal.Z\0"));
+        let length = exr.len();
+        assert_relative_eq!(read_f32(&exr, length - 12), 1.0);
+        assert_relative_eq!(read_f32(&exr, length - 8), 2.0);
+        assert_relative_eq!(read_f32(&exr, length - 4), 3.0);
+    }
+}
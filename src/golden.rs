@@ -0,0 +1,287 @@
+//! Regression checks of rendered images against checked-in references.
+//!
+//! [`Golden::check`] compares a canvas with the reference of the same name,
+//! stored as a Radiance .hdr file, using the perceptual error of
+//! [`compare::flip`](crate::compare::flip). FLIP only sees values up to 1, so
+//! tone mapped copies are compared as well, catching changes to brighter
+//! values like light sources and highlights. When the `BLESS` environment
+//! variable is `1`, references are written instead, so intended changes to
+//! the output can be accepted with `BLESS=1 cargo test`. On mismatch the
+//! failed render and a difference heatmap are written next to the reference.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::canvas::Canvas;
+use crate::compare::{difference_heatmap, flip};
+use crate::hdr::{canvas_from_hdr, hdr_from_canvas, HdrError};
+use crate::ppm::ppm_from_canvas_with;
+use crate::srgb::Encoding;
+use crate::tonemap::{Operator, ToneMapping};
+
+/// Errors from checking an image against its reference.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GoldenError {
+    /// Reading or writing a file failed.
+    Io(PathBuf, String),
+    /// Reference could not be parsed.
+    InvalidReference(PathBuf, HdrError),
+    /// No reference exists yet; run with `BLESS=1` to create it.
+    MissingReference(PathBuf),
+    /// Reference has a different size than the image.
+    SizeMismatch {
+        /// Height and width of the reference
+        expected: (usize, usize),
+        /// Height and width of the image
+        found: (usize, usize),
+    },
+    /// Perceptual error is above the tolerance.
+    Mismatch {
+        /// Reference that was compared against
+        reference: PathBuf,
+        /// Mean perceptual error
+        error: f32,
+        /// Allowed mean perceptual error
+        tolerance: f32,
+    },
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GoldenError::Io(path, message) => write!(f, "{}: {}", path.display(), message),
+            GoldenError::InvalidReference(path, error) => {
+                write!(f, "{}: {}", path.display(), error)
+            }
+            GoldenError::MissingReference(path) => write!(
+                f,
+                "missing reference {}, run with BLESS=1 to create it",
+                path.display()
+            ),
+            GoldenError::SizeMismatch { expected, found } => write!(
+                f,
+                "reference is {}x{} but image is {}x{}",
+                expected.0, expected.1, found.0, found.1
+            ),
+            GoldenError::Mismatch {
+                reference,
+                error,
+                tolerance,
+            } => write!(
+                f,
+                "image differs from {} with error {} above {}",
+                reference.display(),
+                error,
+                tolerance
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+/// Directory of reference images and how strictly to compare with them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Golden {
+    /// Directory holding the references
+    pub directory: PathBuf,
+    /// Largest mean perceptual error accepted
+    pub tolerance: f32,
+    /// Tone mapping for the comparison of values above 1
+    pub tone_mapping: ToneMapping,
+    /// Whether to overwrite references instead of comparing
+    pub bless: bool,
+}
+
+impl Golden {
+    /// Construct a check against references in a directory.
+    ///
+    /// Blessing is enabled if the `BLESS` environment variable is `1`.
+    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+            tolerance: 0.005,
+            tone_mapping: ToneMapping::new(0.0, Operator::Reinhard),
+            bless: std::env::var("BLESS").as_deref() == Ok("1"),
+        }
+    }
+
+    /// Set largest mean perceptual error accepted.
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Set tone mapping for the comparison of values above 1.
+    pub fn with_tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Set whether to overwrite references instead of comparing.
+    pub fn with_bless(mut self, bless: bool) -> Self {
+        self.bless = bless;
+        self
+    }
+
+    /// Return path of the reference with a given name.
+    pub fn reference_path(&self, name: &str) -> PathBuf {
+        self.directory.join(format!("{}.hdr", name))
+    }
+
+    /// Compare image with its reference, or write the reference when blessing.
+    pub fn check(&self, name: &str, image: &Canvas) -> Result<(), GoldenError> {
+        let path = self.reference_path(name);
+        let bytes = hdr_from_canvas(image);
+        if self.bless {
+            fs::create_dir_all(&self.directory)
+                .map_err(|error| GoldenError::Io(self.directory.clone(), error.to_string()))?;
+            return write(&path, &bytes);
+        }
+        if !path.exists() {
+            return Err(GoldenError::MissingReference(path));
+        }
+        let reference =
+            fs::read(&path).map_err(|error| GoldenError::Io(path.clone(), error.to_string()))?;
+        let reference = canvas_from_hdr(&reference)
+            .map_err(|error| GoldenError::InvalidReference(path.clone(), error))?;
+        // Compare after the same quantization the reference went through, so
+        // a freshly blessed image matches exactly.
+        let image = canvas_from_hdr(&bytes).expect("written HDR is valid");
+        if (reference.height, reference.width) != (image.height, image.width) {
+            return Err(GoldenError::SizeMismatch {
+                expected: (reference.height, reference.width),
+                found: (image.height, image.width),
+            });
+        }
+        // FLIP clamps to [0, 1], so also compare tone mapped copies, which keep
+        // differences above 1 visible. The larger error counts.
+        let reference_mapped = self.tone_mapping.apply(&reference);
+        let image_mapped = self.tone_mapping.apply(&image);
+        let clamped_error = flip(&reference, &image);
+        let mapped_error = flip(&reference_mapped, &image_mapped);
+        let (error, left, right) = if mapped_error > clamped_error {
+            (mapped_error, &reference_mapped, &image_mapped)
+        } else {
+            (clamped_error, &reference, &image)
+        };
+        if error > self.tolerance {
+            write(&self.directory.join(format!("{}.failed.hdr", name)), &bytes)?;
+            // Heatmap colors are display values, so they are written as is.
+            let heatmap = difference_heatmap(left, right, 1.0);
+            let diff = ppm_from_canvas_with(&heatmap, &ToneMapping::default(), Encoding::Linear);
+            write(
+                &self.directory.join(format!("{}.diff.ppm", name)),
+                diff.as_bytes(),
+            )?;
+            return Err(GoldenError::Mismatch {
+                reference: path,
+                error,
+                tolerance: self.tolerance,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<(), GoldenError> {
+    fs::write(path, bytes).map_err(|error| GoldenError::Io(path.to_path_buf(), error.to_string()))
+}
+
+#[cfg(test)]
+mod test_golden {
+    use crate::color::Color;
+    use crate::draw::{fill_circle, Brush};
+
+    use super::*;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("golden-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn scene(radius: isize) -> Canvas {
+        let mut canvas = Canvas::new(24, 24);
+        canvas.fill(Color::new(0.1, 0.1, 0.3));
+        fill_circle(
+            &mut canvas,
+            (12, 12),
+            radius,
+            Brush::new(Color::new(1.0, 0.2, 0.1)),
+        );
+        canvas
+    }
+
+    #[test]
+    fn bless_then_check() {
+        let directory = directory("bless");
+        let golden = Golden::new(&directory).with_bless(false);
+        assert!(
+            golden.check("spheres", &scene(6))
+                == Err(GoldenError::MissingReference(directory.join("spheres.hdr")))
+        );
+        golden
+            .clone()
+            .with_bless(true)
+            .check("spheres", &scene(6))
+            .unwrap();
+        assert!(golden.check("spheres", &scene(6)).is_ok());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn mismatch_writes_diff() {
+        let directory = directory("mismatch");
+        let golden = Golden::new(&directory).with_bless(true);
+        golden.check("spheres", &scene(6)).unwrap();
+        let golden = golden.with_bless(false);
+        match golden.check("spheres", &scene(8)) {
+            Err(GoldenError::Mismatch { error, .. }) => assert!(error > golden.tolerance),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(directory.join("spheres.failed.hdr").exists());
+        assert!(directory.join("spheres.diff.ppm").exists());
+        assert!(golden
+            .clone()
+            .with_tolerance(1.0)
+            .check("spheres", &scene(8))
+            .is_ok());
+        assert!(
+            golden.check("spheres", &Canvas::new(2, 3))
+                == Err(GoldenError::SizeMismatch {
+                    expected: (24, 24),
+                    found: (2, 3)
+                })
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn mismatch_above_one() {
+        let directory = directory("bright");
+        let light = |value: f32| {
+            let mut canvas = scene(6);
+            fill_circle(
+                &mut canvas,
+                (12, 12),
+                4,
+                Brush::new(Color::new(value, value, value)),
+            );
+            canvas
+        };
+        let golden = Golden::new(&directory).with_bless(true);
+        golden.check("light", &light(2.0)).unwrap();
+        let golden = golden.with_bless(false);
+        // Both lights are white after clamping, only tone mapping tells them apart.
+        assert!(flip(&light(2.0), &light(10.0)) == 0.0);
+        match golden.check("light", &light(10.0)) {
+            Err(GoldenError::Mismatch { error, .. }) => assert!(error > golden.tolerance),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(golden.check("light", &light(2.0)).is_ok());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+//! For reading and writing canvas as Radiance .hdr (RGBE) file.
+//!
+//! Colors are stored as three 8-bit mantissas with a shared exponent, which
+//! keeps values above 1.0 intact. Both flat and run-length encoded scanlines
+//! can be read; files are written flat.
+
+use std::fmt;
+
+use crate::{canvas::Canvas, color::Color, tonemap::ToneMapping};
+
+/// Errors that can occur while parsing an HDR file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HdrError {
+    /// File does not start with a Radiance signature.
+    InvalidSignature,
+    /// Pixel format other than 32-bit RGBE.
+    UnsupportedFormat(String),
+    /// Resolution line is missing or not in `-Y height +X width` order.
+    InvalidResolution(String),
+    /// Pixel data ended early or has a corrupt run.
+    InvalidData,
+}
+
+impl fmt::Display for HdrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HdrError::InvalidSignature => write!(f, "missing Radiance HDR signature"),
+            HdrError::UnsupportedFormat(format) => {
+                write!(f, "unsupported HDR pixel format {:?}", format)
+            }
+            HdrError::InvalidResolution(line) => {
+                write!(f, "unsupported HDR resolution line {:?}", line)
+            }
+            HdrError::InvalidData => write!(f, "truncated or corrupt HDR pixel data"),
+        }
+    }
+}
+
+impl std::error::Error for HdrError {}
+
+/// Reads canvas pixels and writes to HDR bytes.
+///
+/// Values above the largest one RGBE can hold, including infinity, are
+/// clamped to it, and NaN is written as zero.
+pub fn hdr_from_canvas(canvas: &Canvas) -> Vec<u8> {
+    hdr_from_canvas_with(canvas, &ToneMapping::default())
+}
+
+/// Reads canvas pixels, tone maps them and writes to HDR bytes.
+///
+/// Values stay linear, as HDR images are never display encoded.
+pub fn hdr_from_canvas_with(canvas: &Canvas, tone_mapping: &ToneMapping) -> Vec<u8> {
+    let mut bytes = format!(
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        canvas.height, canvas.width
+    )
+    .into_bytes();
+    for pixel in canvas.pixels.iter() {
+        bytes.extend_from_slice(&color_to_rgbe(tone_mapping.map_color(*pixel)));
+    }
+    bytes
+}
+
+/// Parse HDR bytes into a canvas.
+pub fn canvas_from_hdr(hdr: &[u8]) -> Result<Canvas, HdrError> {
+    let mut position = 0;
+    let mut next_line = || -> Result<String, HdrError> {
+        let rest = hdr.get(position..).ok_or(HdrError::InvalidData)?;
+        let end = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or(HdrError::InvalidData)?;
+        position += end + 1;
+        Ok(String::from_utf8_lossy(&rest[..end]).trim().to_string())
+    };
+
+    let signature = next_line()?;
+    if signature != "#?RADIANCE" && signature != "#?RGBE" {
+        return Err(HdrError::InvalidSignature);
+    }
+    // Header variables until the first empty line.
+    loop {
+        let line = next_line()?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(HdrError::UnsupportedFormat(format.to_string()));
+            }
+        }
+    }
+    let resolution = next_line()?;
+    let (height, width) = parse_resolution(&resolution)
+        .ok_or_else(|| HdrError::InvalidResolution(resolution.clone()))?;
+
+    let mut data = &hdr[position..];
+    // Check the size against the data before allocating, so that a corrupt
+    // header cannot request an absurd canvas.
+    let needed = height.checked_mul(minimum_scanline_bytes(width));
+    if needed.is_none_or(|needed| needed > data.len()) {
+        return Err(HdrError::InvalidData);
+    }
+
+    let mut canvas = Canvas::new(height, width);
+    let mut scanline = vec![[0u8; 4]; width];
+    for row in canvas.pixels.chunks_mut(width.max(1)) {
+        data = read_scanline(data, &mut scanline)?;
+        for (pixel, rgbe) in row.iter_mut().zip(scanline.iter()) {
+            *pixel = rgbe_to_color(*rgbe);
+        }
+    }
+    Ok(canvas)
+}
+
+fn parse_resolution(line: &str) -> Option<(usize, usize)> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    match parts.as_slice() {
+        ["-Y", height, "+X", width] => Some((height.parse().ok()?, width.parse().ok()?)),
+        _ => None,
+    }
+}
+
+/// Fewest bytes a scanline of the given width can be stored in.
+fn minimum_scanline_bytes(width: usize) -> usize {
+    if (8..0x8000).contains(&width) {
+        // Run-length header, then runs of up to 127 values per channel.
+        4 + 4 * 2 * width.div_ceil(127)
+    } else {
+        width.saturating_mul(4)
+    }
+}
+
+/// Read one scanline, returning the remaining data.
+fn read_scanline<'a>(data: &'a [u8], scanline: &mut [[u8; 4]]) -> Result<&'a [u8], HdrError> {
+    let width = scanline.len();
+    let is_rle = (8..0x8000).contains(&width)
+        && data.len() >= 4
+        && data[0] == 2
+        && data[1] == 2
+        && data[2] & 0x80 == 0;
+    if !is_rle {
+        let bytes = data.get(..width * 4).ok_or(HdrError::InvalidData)?;
+        for (pixel, rgbe) in scanline.iter_mut().zip(bytes.chunks(4)) {
+            pixel.copy_from_slice(rgbe);
+        }
+        return Ok(&data[width * 4..]);
+    }
+
+    if ((data[2] as usize) << 8 | data[3] as usize) != width {
+        return Err(HdrError::InvalidData);
+    }
+    let mut data = &data[4..];
+    // Each channel is encoded separately.
+    for channel in 0..4 {
+        let mut column = 0;
+        while column < width {
+            let (&count, rest) = data.split_first().ok_or(HdrError::InvalidData)?;
+            if count > 128 {
+                let run = (count - 128) as usize;
+                let &value = rest.first().ok_or(HdrError::InvalidData)?;
+                let pixels = scanline
+                    .get_mut(column..column + run)
+                    .ok_or(HdrError::InvalidData)?;
+                for pixel in pixels {
+                    pixel[channel] = value;
+                }
+                column += run;
+                data = &rest[1..];
+            } else {
+                let run = count as usize;
+                if run == 0 {
+                    return Err(HdrError::InvalidData);
+                }
+                let values = rest.get(..run).ok_or(HdrError::InvalidData)?;
+                let pixels = scanline
+                    .get_mut(column..column + run)
+                    .ok_or(HdrError::InvalidData)?;
+                for (pixel, value) in pixels.iter_mut().zip(values) {
+                    pixel[channel] = *value;
+                }
+                column += run;
+                data = &rest[run..];
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// Largest value RGBE can hold, a full mantissa with the largest exponent.
+const MAX_RGBE: f32 = 1.694_765_7e38; // 255 * 2^119
+
+fn color_to_rgbe(color: Color<f32>) -> [u8; 4] {
+    let channel = |value: f32| {
+        if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(0.0, MAX_RGBE)
+        }
+    };
+    let color = Color::new(channel(color.r), channel(color.g), channel(color.b));
+    let max = color.r.max(color.g).max(color.b);
+    if max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // Split max into mantissa in [0.5, 1) and exponent.
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2.0_f32.powi(exponent);
+    [
+        (color.r * scale) as u8,
+        (color.g * scale) as u8,
+        (color.b * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color<f32> {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2.0_f32.powi(rgbe[3] as i32 - (128 + 8));
+    Color::new(
+        rgbe[0] as f32 * scale,
+        rgbe[1] as f32 * scale,
+        rgbe[2] as f32 * scale,
+    )
+}
+
+#[cfg(test)]
+mod test_hdr {
+    use crate::{canvas::Canvas, color::Color};
+
+    use super::*;
+
+    #[test]
+    fn test_rgbe_round_trip() {
+        let color = Color::new(10.0, 1.0, 0.5);
+        assert_eq!(color_to_rgbe(color), [160, 16, 8, 132]);
+        assert_relative_eq!(rgbe_to_color(color_to_rgbe(color)), color);
+        assert_eq!(color_to_rgbe(Color::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_rgbe_out_of_range() {
+        let max = rgbe_to_color([255, 255, 255, 255]).r;
+        assert_relative_eq!(max, MAX_RGBE);
+        let infinite = Color::new(f32::INFINITY, 1.0, f32::NAN);
+        assert_eq!(color_to_rgbe(infinite), [255, 0, 0, 255]);
+        assert_eq!(
+            color_to_rgbe(Color::new(f32::MAX, 0.0, 0.0)),
+            [255, 0, 0, 255]
+        );
+        assert_eq!(color_to_rgbe(Color::new(f32::NAN, -1.0, 0.0)), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_hdr_round_trip() {
+        let mut canvas = Canvas::new(2, 3);
+        canvas[(0, 0)] = Color::new(4.0, 2.0, 1.0);
+        canvas[(1, 2)] = Color::new(0.25, 0.5, 0.125);
+        let result = canvas_from_hdr(&hdr_from_canvas(&canvas)).unwrap();
+        assert!(result.height == 2);
+        assert!(result.width == 3);
+        assert_relative_eq!(result[(0, 0)], canvas[(0, 0)]);
+        assert_relative_eq!(result[(1, 2)], canvas[(1, 2)]);
+        assert_relative_eq!(result[(1, 1)], Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_hdr_from_canvas_with_tone_mapping() {
+        let mut canvas = Canvas::new(1, 2);
+        canvas[(0, 1)] = Color::new(1.0, 3.0, 0.0);
+        let tone_mapping = ToneMapping::new(0.0, crate::tonemap::Operator::Reinhard);
+        let result = canvas_from_hdr(&hdr_from_canvas_with(&canvas, &tone_mapping)).unwrap();
+        assert_relative_eq!(result[(0, 0)], Color::new(0.0, 0.0, 0.0));
+        assert_relative_eq!(result[(0, 1)], Color::new(0.5, 0.75, 0.0));
+    }
+
+    #[test]
+    fn test_canvas_from_run_length_encoded_hdr() {
+        let mut hdr = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+        hdr.extend_from_slice(&[2, 2, 0, 8]);
+        // Red: run of eight.
+        hdr.extend_from_slice(&[136, 128]);
+        // Green: literal run of eight.
+        hdr.extend_from_slice(&[8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        // Blue: two runs of four.
+        hdr.extend_from_slice(&[132, 0, 132, 64]);
+        // Exponent: run of eight.
+        hdr.extend_from_slice(&[136, 129]);
+        let canvas = canvas_from_hdr(&hdr).unwrap();
+        assert_relative_eq!(canvas[(0, 0)], Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(canvas[(0, 7)], Color::new(1.0, 0.875, 0.5));
+    }
+
+    #[test]
+    fn test_canvas_from_invalid_hdr() {
+        assert!(canvas_from_hdr(b"P3\n").err() == Some(HdrError::InvalidSignature));
+        assert!(
+            canvas_from_hdr(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n").err()
+                == Some(HdrError::UnsupportedFormat("32-bit_rle_xyze".to_string()))
+        );
+        assert!(
+            canvas_from_hdr(b"#?RADIANCE\n\n+Y 1 +X 1\n").err()
+                == Some(HdrError::InvalidResolution("+Y 1 +X 1".to_string()))
+        );
+        assert!(
+            canvas_from_hdr(b"#?RADIANCE\n\n-Y 1 +X 2\n\x80\x80").err()
+                == Some(HdrError::InvalidData)
+        );
+        // Sizes the data cannot hold are rejected before allocating.
+        let huge = format!(
+            "#?RADIANCE\n\n-Y {} +X {}\n\x02\x02",
+            usize::MAX,
+            usize::MAX
+        );
+        assert!(canvas_from_hdr(huge.as_bytes()).err() == Some(HdrError::InvalidData));
+        assert!(
+            canvas_from_hdr(b"#?RADIANCE\n\n-Y 100000 +X 100000\n\x02\x02\x00\x08").err()
+                == Some(HdrError::InvalidData)
+        );
+    }
+}
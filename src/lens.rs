@@ -0,0 +1,231 @@
+//! Thin lens model for depth of field.
+//!
+//! Points on the focal plane stay sharp, everything else is blurred into the
+//! shape of the aperture (bokeh). Lens sampling happens in camera space, with
+//! the camera looking down the negative z-axis and the lens in the z = 0
+//! plane.
+
+use std::f32::consts::PI;
+
+use crate::canvas::Canvas;
+use crate::distribution::Distribution2D;
+use crate::sampling::concentric_disk;
+use crate::vectors::Vec3;
+
+/// Shape of the lens aperture.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApertureShape {
+    /// Circular aperture.
+    Disk,
+    /// Regular polygon with the given number of blades, rotated in radians.
+    Polygon {
+        /// Number of blades, at least three
+        blades: u32,
+        /// Rotation of the first corner from the x-axis
+        rotation: f32,
+    },
+    /// Aperture mask read from an image, brighter texels pass more light.
+    Image(Distribution2D),
+}
+
+impl ApertureShape {
+    /// Construct an aperture mask from the luminance of a canvas.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the canvas is empty.
+    pub fn from_canvas(canvas: &Canvas) -> Self {
+        let weights: Vec<f32> = canvas
+            .pixels
+            .iter()
+            .map(|pixel| pixel.luminance())
+            .collect();
+        ApertureShape::Image(Distribution2D::new(&weights, canvas.width, canvas.height))
+    }
+
+    /// Map two uniform samples in [0, 1) to a point on the aperture.
+    ///
+    /// Points lie within the unit disk, or the unit square for images.
+    pub fn sample(&self, u1: f32, u2: f32) -> (f32, f32) {
+        match self {
+            ApertureShape::Disk => concentric_disk(u1, u2).0,
+            ApertureShape::Polygon { blades, rotation } => polygon(*blades, *rotation, u1, u2),
+            ApertureShape::Image(distribution) => {
+                let ((x, y), _) = distribution.sample_continuous(u1, u2);
+                // Image rows run downwards.
+                (2.0 * x - 1.0, 1.0 - 2.0 * y)
+            }
+        }
+    }
+}
+
+/// Thin lens with a finite aperture focused at a distance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThinLens {
+    /// Diameter of the aperture, zero for a pinhole
+    pub aperture: f32,
+    /// Distance to the plane in focus
+    pub focal_distance: f32,
+    /// Shape of the aperture
+    pub shape: ApertureShape,
+}
+
+impl ThinLens {
+    /// Construct a new thin lens with a circular aperture.
+    pub fn new(aperture: f32, focal_distance: f32) -> Self {
+        Self {
+            aperture,
+            focal_distance,
+            shape: ApertureShape::Disk,
+        }
+    }
+
+    /// Return lens with the given aperture shape.
+    pub fn with_shape(mut self, shape: ApertureShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Set the focal distance so that a point is in focus.
+    ///
+    /// Takes the distance to the first hit along the ray through the chosen
+    /// pixel, and the normalized camera space direction of that ray.
+    pub fn focus_on(&mut self, hit_distance: f32, direction: &Vec3<f32>) {
+        // The focal plane is perpendicular to the view axis, not a sphere.
+        self.focal_distance = hit_distance * -direction.z;
+    }
+
+    /// Turn a pinhole ray into a ray through a point on the lens.
+    ///
+    /// Takes the camera space direction of the pinhole ray, which must point
+    /// along negative z, and two uniform samples. Returns the ray origin on the
+    /// lens and its normalized direction, both in camera space.
+    pub fn sample_ray(&self, direction: &Vec3<f32>, u1: f32, u2: f32) -> (Vec3<f32>, Vec3<f32>) {
+        let origin = Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        if self.aperture <= 0.0 {
+            return (origin, direction.normalize());
+        }
+        let focus = *direction * (self.focal_distance / -direction.z);
+        let (x, y) = self.shape.sample(u1, u2);
+        let radius = 0.5 * self.aperture;
+        let origin = Vec3 {
+            x: x * radius,
+            y: y * radius,
+            z: 0.0,
+        };
+        (origin, (focus - origin).normalize())
+    }
+}
+
+/// Map the unit square uniformly to a regular polygon inscribed in the unit circle.
+fn polygon(blades: u32, rotation: f32, u1: f32, u2: f32) -> (f32, f32) {
+    let blades = blades.max(3);
+    // Pick a triangle between the center and one blade edge, reusing u1.
+    let scaled = u1 * blades as f32;
+    let blade = (scaled as u32).min(blades - 1);
+    let u1 = scaled - blade as f32;
+
+    let step = 2.0 * PI / blades as f32;
+    let angle = rotation + blade as f32 * step;
+    let (x1, y1) = (angle.cos(), angle.sin());
+    let (x2, y2) = ((angle + step).cos(), (angle + step).sin());
+
+    // Uniform point in the triangle (center, corner1, corner2).
+    let root = u1.sqrt();
+    let (b1, b2) = (root * (1.0 - u2), root * u2);
+    (b1 * x1 + b2 * x2, b1 * y1 + b2 * y2)
+}
+
+#[cfg(test)]
+mod test_lens {
+    use std::f32::consts::PI;
+
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::rng::Pcg32;
+    use crate::vectors::Vec3;
+
+    use super::*;
+
+    fn vector(x: f32, y: f32, z: f32) -> Vec3<f32> {
+        Vec3 { x, y, z }
+    }
+
+    #[test]
+    fn polygon_samples_stay_inside_polygon() {
+        let shape = ApertureShape::Polygon {
+            blades: 6,
+            rotation: 0.3,
+        };
+        // Distance from the center to each edge of the hexagon.
+        let apothem = (PI / 6.0).cos();
+        let mut rng = Pcg32::new(5);
+        for _ in 0..2000 {
+            let (x, y) = shape.sample(rng.next_f32(), rng.next_f32());
+            for blade in 0..6 {
+                let normal = 0.3 + (blade as f32 + 0.5) * 2.0 * PI / 6.0;
+                assert!(x * normal.cos() + y * normal.sin() <= apothem + 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn image_aperture_samples_bright_texels() {
+        let mut canvas = Canvas::new(4, 4);
+        // Only the upper right texel lets light through.
+        canvas[(0, 3)] = Color::new(1.0, 1.0, 1.0);
+        let shape = ApertureShape::from_canvas(&canvas);
+        let mut rng = Pcg32::new(9);
+        for _ in 0..100 {
+            let (x, y) = shape.sample(rng.next_f32(), rng.next_f32());
+            assert!(x >= 0.5 && y >= 0.5);
+        }
+    }
+
+    #[test]
+    fn lens_rays_converge_on_focal_plane() {
+        let lens = ThinLens::new(0.5, 4.0);
+        let direction = vector(0.2, -0.1, -1.0);
+        let focus = direction * 4.0;
+        let mut rng = Pcg32::new(11);
+        for _ in 0..10 {
+            let (origin, ray) = lens.sample_ray(&direction, rng.next_f32(), rng.next_f32());
+            assert!(origin.x * origin.x + origin.y * origin.y <= 0.0625 + 1e-6);
+            // Follow the ray to z = -4.
+            let t = -4.0 / ray.z;
+            assert_relative_eq!(origin + ray * t, focus, epsilon = 1e-4);
+        }
+    }
+
+    #[test]
+    fn pinhole_lens_keeps_ray() {
+        let lens = ThinLens::new(0.0, 4.0);
+        let (origin, ray) = lens.sample_ray(&vector(0.0, 0.0, -2.0), 0.3, 0.7);
+        assert_relative_eq!(origin, vector(0.0, 0.0, 0.0));
+        assert_relative_eq!(ray, vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn lens_samples_are_deterministic_for_seed() {
+        let lens = ThinLens::new(1.0, 2.0);
+        let direction = vector(0.1, 0.1, -1.0);
+        let trace = |seed| {
+            let mut rng = Pcg32::new(seed);
+            lens.sample_ray(&direction, rng.next_f32(), rng.next_f32())
+        };
+        assert!(trace(42) == trace(42));
+        assert!(trace(42) != trace(43));
+    }
+
+    #[test]
+    fn autofocus_uses_distance_along_view_axis() {
+        let mut lens = ThinLens::new(0.5, 1.0);
+        let direction = vector(0.6, 0.0, -0.8);
+        lens.focus_on(5.0, &direction);
+        assert_relative_eq!(lens.focal_distance, 4.0);
+    }
+}
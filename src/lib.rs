@@ -13,7 +13,38 @@ extern crate approx;
 #[cfg(not(test))]
 extern crate approx;
 
+pub mod animation;
+pub mod aov;
+pub mod blackbody;
+pub mod bump;
 pub mod canvas;
+pub mod cie;
 pub mod color;
+pub mod colorspace;
+pub mod compare;
+pub mod denoise;
+pub mod distribution;
+pub mod draw;
+pub mod environment;
+pub mod exr;
+pub mod golden;
+pub mod hdr;
+pub mod lens;
+pub mod noise;
+pub mod pam;
+pub mod png;
+pub mod postprocess;
 pub mod ppm;
+pub mod quaternion;
+pub mod rgba;
+pub mod rng;
+pub mod sampling;
+pub mod sanitize;
+#[cfg(feature = "spectral")]
+pub mod spectrum;
+pub mod srgb;
+pub mod statistics;
+pub mod texture;
+pub mod tonemap;
+pub mod uv;
 pub mod vectors;
@@ -0,0 +1,65 @@
+//! For saving RGBA canvas to .pam (Netpbm portable arbitrary map) file.
+//!
+//! PAM is the Netpbm format that can hold an alpha channel. Colors are
+//! written with straight alpha, sRGB encoded unless another encoding is
+//! chosen.
+
+use crate::rgba::RgbaCanvas;
+use crate::srgb::Encoding;
+use crate::tonemap::ToneMapping;
+
+/// Reads canvas pixels and writes to PAM bytes.
+///
+/// Colors are written sRGB encoded, without tone mapping.
+pub fn pam_from_canvas(canvas: &RgbaCanvas) -> Vec<u8> {
+    pam_from_canvas_with(
+        canvas,
+        &ToneMapping::default(),
+        Encoding::for_extension("pam"),
+    )
+}
+
+/// Reads canvas pixels, tone maps and encodes them and writes to PAM bytes.
+pub fn pam_from_canvas_with(
+    canvas: &RgbaCanvas,
+    tone_mapping: &ToneMapping,
+    encoding: Encoding,
+) -> Vec<u8> {
+    let mut bytes = format!(
+        "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+        canvas.width, canvas.height
+    )
+    .into_bytes();
+    for pixel in canvas.pixels.iter() {
+        bytes.extend_from_slice(&pixel.tone_mapped(tone_mapping).to_rgba8(encoding));
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod test_pam {
+    use crate::color::Color;
+    use crate::rgba::{Rgba, RgbaCanvas};
+
+    use super::*;
+
+    #[test]
+    fn test_pam_from_canvas() {
+        let mut canvas = RgbaCanvas::new(1, 2);
+        canvas[(0, 1)] = Rgba::from_straight(Color::new(1.0, 0.0, 0.5), 0.5);
+        let pam = pam_from_canvas(&canvas);
+        let header = "P7\nWIDTH 2\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n";
+        assert!(pam.starts_with(header.as_bytes()));
+        assert!(pam[header.len()..] == [0, 0, 0, 0, 255, 0, 188, 128]);
+    }
+
+    #[test]
+    fn test_pam_from_canvas_with_tone_mapping() {
+        let mut canvas = RgbaCanvas::new(1, 2);
+        canvas[(0, 1)] = Rgba::from_straight(Color::new(1.0, 0.0, 0.5), 0.5);
+        let tone_mapping = ToneMapping::new(1.0, crate::tonemap::Operator::Reinhard);
+        let pam = pam_from_canvas_with(&canvas, &tone_mapping, Encoding::Linear);
+        let header_length = pam.len() - 8;
+        assert!(pam[header_length..] == [0, 0, 0, 0, 170, 0, 128, 128]);
+    }
+}
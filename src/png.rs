@@ -0,0 +1,143 @@
+//! For saving RGBA canvas to .png file.
+//!
+//! Pixels are written as 8-bit sRGB with straight alpha, unless another
+//! encoding is chosen. Image data is
+//! stored in uncompressed deflate blocks, which every decoder reads and
+//! which keeps the writer free of a compression library.
+
+use crate::rgba::RgbaCanvas;
+use crate::srgb::Encoding;
+use crate::tonemap::ToneMapping;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Largest payload of a stored deflate block.
+const MAX_BLOCK: usize = 65_535;
+
+/// Reads canvas pixels and writes to PNG bytes.
+///
+/// Colors are written sRGB encoded, without tone mapping.
+pub fn png_from_canvas(canvas: &RgbaCanvas) -> Vec<u8> {
+    png_from_canvas_with(
+        canvas,
+        &ToneMapping::default(),
+        Encoding::for_extension("png"),
+    )
+}
+
+/// Reads canvas pixels, tone maps and encodes them and writes to PNG bytes.
+pub fn png_from_canvas_with(
+    canvas: &RgbaCanvas,
+    tone_mapping: &ToneMapping,
+    encoding: Encoding,
+) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(&(canvas.width as u32).to_be_bytes());
+    header.extend_from_slice(&(canvas.height as u32).to_be_bytes());
+    // 8 bits per channel, color type 6 (RGBA), default compression, filter
+    // and no interlacing.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut scanlines = Vec::with_capacity(canvas.height * (canvas.width * 4 + 1));
+    for row in canvas.pixels.chunks(canvas.width.max(1)) {
+        // Filter type 0, bytes are stored as they are.
+        scanlines.push(0);
+        for pixel in row {
+            scanlines.extend_from_slice(&pixel.tone_mapped(tone_mapping).to_rgba8(encoding));
+        }
+    }
+
+    let mut bytes = SIGNATURE.to_vec();
+    write_chunk(&mut bytes, b"IHDR", &header);
+    write_chunk(&mut bytes, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut bytes, b"IEND", &[]);
+    bytes
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = bytes.len();
+    bytes.extend_from_slice(kind);
+    bytes.extend_from_slice(data);
+    let crc = crc32(&bytes[start..]);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wrap data in a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        stream.push(last as u8);
+        let length = block.len() as u16;
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65_521;
+        b = (b + a) % 65_521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test_png {
+    use crate::color::Color;
+    use crate::rgba::{Rgba, RgbaCanvas};
+
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_zlib_stored_splits_blocks() {
+        let data = vec![7; MAX_BLOCK + 10];
+        let stream = zlib_stored(&data);
+        assert_eq!(stream.len(), 2 + 5 + MAX_BLOCK + 5 + 10 + 4);
+        assert_eq!(stream[2], 0);
+        assert_eq!(stream[2 + 5 + MAX_BLOCK], 1);
+    }
+
+    #[test]
+    fn test_png_from_canvas() {
+        let mut canvas = RgbaCanvas::new(1, 2);
+        canvas[(0, 0)] = Rgba::from_straight(Color::new(1.0, 0.0, 0.5), 0.5);
+        let png = png_from_canvas(&canvas);
+        assert!(png[..8] == SIGNATURE);
+        assert!(png[12..16] == *b"IHDR");
+        assert!(png[16..25] == [0, 0, 0, 2, 0, 0, 0, 1, 8]);
+        let idat = &png[37..];
+        assert!(idat[..4] == *b"IDAT");
+        // zlib header, block header, filter byte and the two pixels.
+        assert!(idat[4..20] == [0x78, 1, 1, 9, 0, 0xf6, 0xff, 0, 255, 0, 188, 128, 0, 0, 0, 0]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+    }
+}
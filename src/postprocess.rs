@@ -0,0 +1,667 @@
+//! Post-processing filters applied to a rendered canvas.
+//!
+//! Filters work on linear HDR values, so they belong before tone mapping.
+//! An [`Effect`] is a single filter with its parameters, and a [`PostProcess`]
+//! chains effects. Chains can be parsed from text like
+//! `bloom:threshold=1,intensity=0.3;vignette:strength=0.4`, so that they can be
+//! given on a command line or in a scene file.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::rng::Pcg32;
+use crate::texture::{ImageTexture, WrapMode};
+
+/// Largest blur radius in pixels, larger blurs are limited to it.
+pub const MAX_BLUR_RADIUS: usize = 256;
+
+/// Largest number of bloom scales, more are limited to it.
+pub const MAX_BLOOM_LEVELS: usize = 8;
+
+/// Convolution kernel with odd width and height, centered on the middle weight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    /// Width
+    pub width: usize,
+    /// Height
+    pub height: usize,
+    /// Weights row by row
+    pub weights: Vec<f32>,
+}
+
+impl Kernel {
+    /// Construct a kernel from rows of weights.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rows differ in length, or the size is not odd.
+    pub fn new(rows: &[&[f32]]) -> Self {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+        assert!(
+            rows.iter().all(|row| row.len() == width),
+            "kernel rows differ in length"
+        );
+        assert!(width % 2 == 1 && height % 2 == 1, "kernel size must be odd");
+        Self {
+            width,
+            height,
+            weights: rows.concat(),
+        }
+    }
+
+    /// 3x3 sharpening kernel.
+    pub fn sharpen() -> Self {
+        Self::new(&[&[0.0, -1.0, 0.0], &[-1.0, 5.0, -1.0], &[0.0, -1.0, 0.0]])
+    }
+}
+
+/// Convolve the canvas with a kernel, repeating the edge pixels.
+pub fn convolve(canvas: &Canvas, kernel: &Kernel) -> Canvas {
+    let mut result = Canvas::new(canvas.height, canvas.width);
+    let (half_width, half_height) = ((kernel.width / 2) as isize, (kernel.height / 2) as isize);
+    for row in 0..canvas.height {
+        for column in 0..canvas.width {
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            for (k, weight) in kernel.weights.iter().enumerate() {
+                let dy = (k / kernel.width) as isize - half_height;
+                let dx = (k % kernel.width) as isize - half_width;
+                sum = sum + clamped(canvas, row as isize + dy, column as isize + dx) * *weight;
+            }
+            result[(row, column)] = sum;
+        }
+    }
+    result
+}
+
+/// Convolve with a one dimensional kernel horizontally, then vertically.
+///
+/// This is much faster than [`convolve`] for kernels that separate, like blurs.
+pub fn convolve_separable(canvas: &Canvas, kernel: &[f32]) -> Canvas {
+    let half = (kernel.len() / 2) as isize;
+    let pass = |source: &Canvas, horizontal: bool| {
+        let mut result = Canvas::new(source.height, source.width);
+        for row in 0..source.height {
+            for column in 0..source.width {
+                let mut sum = Color::new(0.0, 0.0, 0.0);
+                for (k, weight) in kernel.iter().enumerate() {
+                    let offset = k as isize - half;
+                    let tap = if horizontal {
+                        clamped(source, row as isize, column as isize + offset)
+                    } else {
+                        clamped(source, row as isize + offset, column as isize)
+                    };
+                    sum = sum + tap * *weight;
+                }
+                result[(row, column)] = sum;
+            }
+        }
+        result
+    };
+    pass(&pass(canvas, true), false)
+}
+
+fn clamped(canvas: &Canvas, row: isize, column: isize) -> Color<f32> {
+    let row = row.clamp(0, canvas.height as isize - 1) as usize;
+    let column = column.clamp(0, canvas.width as isize - 1) as usize;
+    canvas[(row, column)]
+}
+
+/// Normalized Gaussian weights reaching three standard deviations out.
+///
+/// The radius is limited to [`MAX_BLUR_RADIUS`], cutting off wider kernels.
+///
+/// # Panics
+///
+/// Panics if sigma is not positive and finite.
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    assert!(
+        sigma.is_finite() && sigma > 0.0,
+        "sigma must be positive and finite"
+    );
+    let radius = (3.0 * sigma).ceil().min(MAX_BLUR_RADIUS as f32) as isize;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.iter().map(|weight| weight / total).collect()
+}
+
+/// Blur with a Gaussian of the given standard deviation in pixels.
+///
+/// Sigma of zero, below or NaN leaves the canvas untouched, and sigma is
+/// limited to a third of [`MAX_BLUR_RADIUS`].
+pub fn gaussian_blur(canvas: &Canvas, sigma: f32) -> Canvas {
+    if sigma.is_nan() || sigma <= 0.0 {
+        return canvas.clone();
+    }
+    let sigma = sigma.min(MAX_BLUR_RADIUS as f32 / 3.0);
+    convolve_separable(canvas, &gaussian_kernel(sigma))
+}
+
+/// Blur with a box reaching `radius` pixels to every side.
+///
+/// The radius is limited to [`MAX_BLUR_RADIUS`].
+pub fn box_blur(canvas: &Canvas, radius: usize) -> Canvas {
+    let size = 2 * radius.min(MAX_BLUR_RADIUS) + 1;
+    convolve_separable(canvas, &vec![1.0 / size as f32; size])
+}
+
+/// Sharpen by adding the difference between the canvas and a blurred copy.
+pub fn unsharp_mask(canvas: &Canvas, sigma: f32, amount: f32) -> Canvas {
+    let mut result = gaussian_blur(canvas, sigma);
+    for (target, original) in result.pixels.iter_mut().zip(canvas.pixels.iter()) {
+        *target = *original + (*original - *target) * amount;
+    }
+    result
+}
+
+/// Add a glow around pixels brighter than a threshold.
+///
+/// The part of the luminance above the threshold is blurred at `levels`
+/// scales, doubling sigma each time, and the average is added back scaled by
+/// intensity. Levels are limited to [`MAX_BLOOM_LEVELS`], and the blurs to
+/// the limits of [`gaussian_blur`].
+pub fn bloom(canvas: &Canvas, threshold: f32, intensity: f32, sigma: f32, levels: usize) -> Canvas {
+    let levels = levels.min(MAX_BLOOM_LEVELS);
+    let mut bright = Canvas::new(canvas.height, canvas.width);
+    for (target, source) in bright.pixels.iter_mut().zip(canvas.pixels.iter()) {
+        let luminance = source.luminance();
+        if luminance > threshold {
+            *target = *source * ((luminance - threshold) / luminance);
+        }
+    }
+    let mut result = canvas.clone();
+    let weight = intensity / levels.max(1) as f32;
+    for level in 0..levels {
+        let blurred = gaussian_blur(&bright, sigma * 2.0_f32.powi(level as i32));
+        for (target, glow) in result.pixels.iter_mut().zip(blurred.pixels.iter()) {
+            *target = *target + *glow * weight;
+        }
+    }
+    result
+}
+
+/// Darken towards the corners.
+///
+/// Pixels are scaled by `1 - strength * d²`, where d is the distance from the
+/// center relative to the distance of the corners.
+pub fn vignette(canvas: &Canvas, strength: f32) -> Canvas {
+    let mut result = canvas.clone();
+    let (center_x, center_y) = (canvas.width as f32 / 2.0, canvas.height as f32 / 2.0);
+    let corner = center_x * center_x + center_y * center_y;
+    for row in 0..canvas.height {
+        for column in 0..canvas.width {
+            let dx = column as f32 + 0.5 - center_x;
+            let dy = row as f32 + 0.5 - center_y;
+            let factor = (1.0 - strength * (dx * dx + dy * dy) / corner).max(0.0);
+            result[(row, column)] = result[(row, column)] * factor;
+        }
+    }
+    result
+}
+
+/// Shift red outwards and blue inwards, like a lens with lateral color error.
+///
+/// Amount is the relative change in distance from the center, 0.005 is subtle.
+pub fn chromatic_aberration(canvas: &Canvas, amount: f32) -> Canvas {
+    if canvas.pixels.is_empty() {
+        return canvas.clone();
+    }
+    let texture = ImageTexture::new(canvas.clone()).with_wrap(WrapMode::Clamp);
+    let mut result = Canvas::new(canvas.height, canvas.width);
+    for row in 0..canvas.height {
+        for column in 0..canvas.width {
+            let u = (column as f32 + 0.5) / canvas.width as f32;
+            let v = 1.0 - (row as f32 + 0.5) / canvas.height as f32;
+            let sample =
+                |scale: f32| texture.color_at(0.5 + (u - 0.5) * scale, 0.5 + (v - 0.5) * scale);
+            // Sampling closer to the center pushes the channel outwards.
+            let red = sample(1.0 - amount).r;
+            let blue = sample(1.0 + amount).b;
+            result[(row, column)] = Color::new(red, canvas[(row, column)].g, blue);
+        }
+    }
+    result
+}
+
+/// Add monochrome noise, scaling pixels by up to `1 ± amount`.
+///
+/// The same seed gives the same grain.
+pub fn film_grain(canvas: &Canvas, amount: f32, seed: u64) -> Canvas {
+    let mut rng = Pcg32::new(seed);
+    let mut result = canvas.clone();
+    for pixel in result.pixels.iter_mut() {
+        // Triangular noise in [-1, 1] looks more like grain than uniform noise.
+        let noise = rng.next_f32() + rng.next_f32() - 1.0;
+        *pixel = *pixel * (1.0 + amount * noise);
+    }
+    result
+}
+
+/// Single post-processing filter with its parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    /// See [`gaussian_blur`].
+    GaussianBlur {
+        /// Standard deviation in pixels
+        sigma: f32,
+    },
+    /// See [`box_blur`].
+    BoxBlur {
+        /// Pixels to every side
+        radius: usize,
+    },
+    /// See [`unsharp_mask`].
+    UnsharpMask {
+        /// Standard deviation of the blur in pixels
+        sigma: f32,
+        /// Strength of the sharpening
+        amount: f32,
+    },
+    /// See [`bloom`].
+    Bloom {
+        /// Luminance above which pixels glow
+        threshold: f32,
+        /// Strength of the glow
+        intensity: f32,
+        /// Standard deviation of the smallest blur in pixels
+        sigma: f32,
+        /// Number of blur scales
+        levels: usize,
+    },
+    /// See [`vignette`].
+    Vignette {
+        /// Darkening at the corners
+        strength: f32,
+    },
+    /// See [`chromatic_aberration`].
+    ChromaticAberration {
+        /// Relative shift of red and blue
+        amount: f32,
+    },
+    /// See [`film_grain`].
+    FilmGrain {
+        /// Strength of the grain
+        amount: f32,
+        /// Seed of the noise
+        seed: u64,
+    },
+    /// See [`convolve`].
+    Convolve(Kernel),
+}
+
+impl Effect {
+    /// Apply the effect to a canvas.
+    pub fn apply(&self, canvas: &Canvas) -> Canvas {
+        match self {
+            Effect::GaussianBlur { sigma } => gaussian_blur(canvas, *sigma),
+            Effect::BoxBlur { radius } => box_blur(canvas, *radius),
+            Effect::UnsharpMask { sigma, amount } => unsharp_mask(canvas, *sigma, *amount),
+            Effect::Bloom {
+                threshold,
+                intensity,
+                sigma,
+                levels,
+            } => bloom(canvas, *threshold, *intensity, *sigma, *levels),
+            Effect::Vignette { strength } => vignette(canvas, *strength),
+            Effect::ChromaticAberration { amount } => chromatic_aberration(canvas, *amount),
+            Effect::FilmGrain { amount, seed } => film_grain(canvas, *amount, *seed),
+            Effect::Convolve(kernel) => convolve(canvas, kernel),
+        }
+    }
+}
+
+/// Errors that can occur while parsing effects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EffectError {
+    /// Effect name is not known.
+    UnknownEffect(String),
+    /// Parameter is not known for the effect.
+    UnknownParameter(String),
+    /// Parameter is not `key=value`, or the value is not a number in range.
+    InvalidParameter(String),
+}
+
+impl fmt::Display for EffectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EffectError::UnknownEffect(name) => write!(f, "unknown effect {:?}", name),
+            EffectError::UnknownParameter(name) => write!(f, "unknown effect parameter {:?}", name),
+            EffectError::InvalidParameter(text) => {
+                write!(f, "invalid effect parameter {:?}", text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EffectError {}
+
+impl FromStr for Effect {
+    type Err = EffectError;
+
+    /// Parse `name` or `name:key=value,key=value`.
+    ///
+    /// Names are `blur`, `box_blur`, `sharpen`, `bloom`, `vignette`,
+    /// `chromatic_aberration` and `grain`, with the parameter names of the
+    /// variants. Missing parameters take defaults. Values must be finite and
+    /// not negative. Radii go up to [`MAX_BLUR_RADIUS`] and sigmas to a third
+    /// of it, and bloom levels up to [`MAX_BLOOM_LEVELS`].
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (name, parameters) = match text.split_once(':') {
+            Some((name, parameters)) => (name.trim(), parameters),
+            None => (text.trim(), ""),
+        };
+        let mut effect = match name {
+            "blur" => Effect::GaussianBlur { sigma: 2.0 },
+            "box_blur" => Effect::BoxBlur { radius: 1 },
+            "sharpen" => Effect::UnsharpMask {
+                sigma: 1.0,
+                amount: 0.5,
+            },
+            "bloom" => Effect::Bloom {
+                threshold: 1.0,
+                intensity: 0.3,
+                sigma: 2.0,
+                levels: 4,
+            },
+            "vignette" => Effect::Vignette { strength: 0.4 },
+            "chromatic_aberration" => Effect::ChromaticAberration { amount: 0.005 },
+            "grain" => Effect::FilmGrain {
+                amount: 0.05,
+                seed: 0,
+            },
+            _ => return Err(EffectError::UnknownEffect(name.to_string())),
+        };
+        for parameter in parameters.split(',').filter(|p| !p.trim().is_empty()) {
+            let invalid = || EffectError::InvalidParameter(parameter.to_string());
+            let (key, value) = parameter.split_once('=').ok_or_else(invalid)?;
+            let (key, value) = (key.trim(), value.trim());
+            let number: f32 = value.parse().map_err(|_| invalid())?;
+            if !number.is_finite() || number < 0.0 {
+                return Err(invalid());
+            }
+            let count = |maximum: usize| match value.parse::<usize>() {
+                Ok(count) if count <= maximum => Ok(count),
+                _ => Err(invalid()),
+            };
+            match (&mut effect, key) {
+                (Effect::GaussianBlur { sigma }, "sigma")
+                | (Effect::UnsharpMask { sigma, .. }, "sigma")
+                | (Effect::Bloom { sigma, .. }, "sigma") => {
+                    if 3.0 * number > MAX_BLUR_RADIUS as f32 {
+                        return Err(invalid());
+                    }
+                    *sigma = number
+                }
+                (Effect::BoxBlur { radius }, "radius") => *radius = count(MAX_BLUR_RADIUS)?,
+                (Effect::UnsharpMask { amount, .. }, "amount")
+                | (Effect::ChromaticAberration { amount }, "amount")
+                | (Effect::FilmGrain { amount, .. }, "amount") => *amount = number,
+                (Effect::Bloom { threshold, .. }, "threshold") => *threshold = number,
+                (Effect::Bloom { intensity, .. }, "intensity") => *intensity = number,
+                (Effect::Bloom { levels, .. }, "levels") => *levels = count(MAX_BLOOM_LEVELS)?,
+                (Effect::Vignette { strength }, "strength") => *strength = number,
+                (Effect::FilmGrain { seed, .. }, "seed") => {
+                    *seed = value.parse().map_err(|_| invalid())?
+                }
+                _ => return Err(EffectError::UnknownParameter(key.to_string())),
+            }
+        }
+        Ok(effect)
+    }
+}
+
+/// Chain of effects applied in order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PostProcess {
+    /// Effects in the order they are applied
+    pub effects: Vec<Effect>,
+}
+
+impl PostProcess {
+    /// Construct an empty chain, which leaves canvases untouched.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an effect.
+    pub fn with(mut self, effect: Effect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Apply all effects in order.
+    pub fn apply(&self, canvas: &Canvas) -> Canvas {
+        let mut result = canvas.clone();
+        for effect in self.effects.iter() {
+            result = effect.apply(&result);
+        }
+        result
+    }
+}
+
+impl FromStr for PostProcess {
+    type Err = EffectError;
+
+    /// Parse effects separated by `;`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let effects = text
+            .split(';')
+            .filter(|effect| !effect.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { effects })
+    }
+}
+
+#[cfg(test)]
+mod test_postprocess {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+
+    use super::*;
+
+    fn gray(value: f32) -> Color<f32> {
+        Color::new(value, value, value)
+    }
+
+    fn impulse(size: usize, value: f32) -> Canvas {
+        let mut canvas = Canvas::new(size, size);
+        canvas[(size / 2, size / 2)] = gray(value);
+        canvas
+    }
+
+    fn total(canvas: &Canvas) -> f32 {
+        canvas.pixels.iter().map(|pixel| pixel.r).sum()
+    }
+
+    #[test]
+    fn convolve_with_kernel() {
+        let identity = Kernel::new(&[&[0.0, 0.0, 0.0], &[0.0, 1.0, 0.0], &[0.0, 0.0, 0.0]]);
+        let canvas = impulse(3, 1.0);
+        assert!(convolve(&canvas, &identity).pixels == canvas.pixels);
+        let shift = Kernel::new(&[&[0.0, 0.0, 1.0]]);
+        let shifted = convolve(&canvas, &shift);
+        assert_relative_eq!(shifted[(1, 0)], gray(1.0));
+        assert_relative_eq!(shifted[(1, 1)], gray(0.0));
+        // Flat areas stay flat under sharpening.
+        let mut flat = Canvas::new(3, 3);
+        flat.fill(gray(0.5));
+        assert_relative_eq!(convolve(&flat, &Kernel::sharpen())[(0, 0)], gray(0.5));
+    }
+
+    #[test]
+    fn blurs_keep_energy() {
+        let canvas = impulse(21, 1.0);
+        let blurred = gaussian_blur(&canvas, 2.0);
+        assert_relative_eq!(total(&blurred), 1.0, epsilon = 1e-5);
+        assert!(blurred[(10, 10)].r < 0.1 && blurred[(10, 12)].r > 0.0);
+        let kernel = gaussian_kernel(2.0);
+        assert!(kernel.len() == 13);
+        assert_relative_eq!(kernel[6], 0.199_68, epsilon = 1e-4);
+
+        let boxed = box_blur(&canvas, 1);
+        assert_relative_eq!(boxed[(9, 11)].r, 1.0 / 9.0, epsilon = 1e-6);
+        assert_relative_eq!(boxed[(8, 10)].r, 0.0);
+    }
+
+    #[test]
+    fn unsharp_mask_increases_contrast() {
+        let mut canvas = Canvas::new(1, 6);
+        for column in 3..6 {
+            canvas[(0, column)] = gray(1.0);
+        }
+        let sharpened = unsharp_mask(&canvas, 1.0, 1.0);
+        assert!(sharpened[(0, 2)].r < 0.0);
+        assert!(sharpened[(0, 3)].r > 1.0);
+        assert_relative_eq!(sharpened[(0, 0)].r, 0.0, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn bloom_spreads_highlights_only() {
+        let canvas = impulse(31, 10.0);
+        let bloomed = bloom(&canvas, 1.0, 0.5, 1.0, 3);
+        // Energy above the threshold is added half again.
+        assert_relative_eq!(total(&bloomed), 10.0 + 0.5 * 9.0, epsilon = 1e-3);
+        assert!(bloomed[(15, 19)].r > 0.0);
+        let dim = impulse(31, 0.5);
+        assert!(bloom(&dim, 1.0, 0.5, 1.0, 3).pixels == dim.pixels);
+    }
+
+    #[test]
+    fn vignette_darkens_corners() {
+        let mut canvas = Canvas::new(10, 10);
+        canvas.fill(gray(1.0));
+        let result = vignette(&canvas, 0.5);
+        assert!(result[(5, 5)].r > 0.99);
+        assert!(result[(0, 0)].r < 0.6 && result[(0, 0)].r > 0.5);
+        assert_relative_eq!(result[(0, 9)], result[(9, 0)]);
+    }
+
+    #[test]
+    fn chromatic_aberration_separates_channels() {
+        let mut canvas = Canvas::new(1, 41);
+        canvas[(0, 35)] = gray(1.0);
+        let result = chromatic_aberration(&canvas, 0.1);
+        // Red moves away from the center, blue towards it.
+        let brightest = |channel: fn(&Color<f32>) -> f32| {
+            (0..41)
+                .max_by(|&a, &b| {
+                    channel(&result[(0, a)])
+                        .partial_cmp(&channel(&result[(0, b)]))
+                        .unwrap()
+                })
+                .unwrap()
+        };
+        assert!(brightest(|c| c.r) > 35);
+        assert!(brightest(|c| c.g) == 35);
+        assert!(brightest(|c| c.b) < 35);
+        // The center does not move.
+        let center = chromatic_aberration(&impulse(5, 1.0), 0.1);
+        assert_relative_eq!(center[(2, 2)], gray(1.0), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn film_grain_is_reproducible() {
+        let mut canvas = Canvas::new(8, 8);
+        canvas.fill(gray(0.5));
+        let a = film_grain(&canvas, 0.2, 1);
+        assert!(a.pixels == film_grain(&canvas, 0.2, 1).pixels);
+        assert!(a.pixels != film_grain(&canvas, 0.2, 2).pixels);
+        for pixel in a.pixels.iter() {
+            assert!((0.4..=0.6).contains(&pixel.r));
+            assert!(pixel.r == pixel.g && pixel.g == pixel.b);
+        }
+    }
+
+    #[test]
+    fn parse_effect_chain() {
+        let chain: PostProcess = "bloom:threshold=2,levels=2; vignette".parse().unwrap();
+        assert!(
+            chain.effects
+                == vec![
+                    Effect::Bloom {
+                        threshold: 2.0,
+                        intensity: 0.3,
+                        sigma: 2.0,
+                        levels: 2,
+                    },
+                    Effect::Vignette { strength: 0.4 },
+                ]
+        );
+        assert!(
+            "grain:seed=7".parse::<Effect>()
+                == Ok(Effect::FilmGrain {
+                    amount: 0.05,
+                    seed: 7
+                })
+        );
+        assert!(
+            "glow".parse::<Effect>().err() == Some(EffectError::UnknownEffect("glow".to_string()))
+        );
+        assert!(
+            "blur:radius=2".parse::<Effect>().err()
+                == Some(EffectError::UnknownParameter("radius".to_string()))
+        );
+        assert!(
+            "box_blur:radius=1.5".parse::<Effect>().err()
+                == Some(EffectError::InvalidParameter("radius=1.5".to_string()))
+        );
+        assert!("".parse::<PostProcess>().unwrap().effects.is_empty());
+    }
+
+    #[test]
+    fn parse_rejects_out_of_range_values() {
+        for text in [
+            "blur:sigma=inf",
+            "blur:sigma=NaN",
+            "blur:sigma=-1",
+            "blur:sigma=1000",
+            "bloom:levels=40",
+            "box_blur:radius=100000",
+            "vignette:strength=-0.5",
+        ]
+        .iter()
+        {
+            let parameter = text.split_once(':').unwrap().1.to_string();
+            assert!(text.parse::<Effect>().err() == Some(EffectError::InvalidParameter(parameter)));
+        }
+        assert!("bloom:levels=8".parse::<Effect>().is_ok());
+    }
+
+    #[test]
+    fn filters_limit_their_inputs() {
+        let canvas = impulse(5, 1.0);
+        assert!(gaussian_blur(&canvas, f32::NAN).pixels == canvas.pixels);
+        assert!(gaussian_kernel(1.0e9).len() == 2 * MAX_BLUR_RADIUS + 1);
+        // Huge blurs are limited, and keep flat areas flat.
+        let mut flat = Canvas::new(3, 3);
+        flat.fill(gray(0.5));
+        assert_relative_eq!(
+            gaussian_blur(&flat, f32::INFINITY)[(1, 1)],
+            gray(0.5),
+            epsilon = 1e-4
+        );
+        assert_relative_eq!(
+            box_blur(&flat, usize::MAX)[(1, 1)],
+            gray(0.5),
+            epsilon = 1e-4
+        );
+        let bloomed = bloom(&impulse(5, 4.0), 1.0, 0.3, 2.0, 40);
+        assert!(bloomed.pixels.iter().all(|pixel| pixel.is_finite()));
+    }
+
+    #[test]
+    fn chain_applies_in_order() {
+        let canvas = impulse(9, 4.0);
+        let chain = PostProcess::new()
+            .with(Effect::BoxBlur { radius: 1 })
+            .with(Effect::Convolve(Kernel::new(&[&[2.0]])));
+        let result = chain.apply(&canvas);
+        assert_relative_eq!(result[(4, 4)], gray(8.0 / 9.0), epsilon = 1e-6);
+        assert!(PostProcess::new().apply(&canvas).pixels == canvas.pixels);
+    }
+}
@@ -1,17 +1,27 @@
-//! For saving canvas to .ppm file
+//! For saving canvas to .ppm file, and reading it back.
 //!
 //!
 
-use crate::{canvas::Canvas, color::Color};
+use std::fmt;
+
+use crate::{canvas::Canvas, color::Color, srgb::Encoding, tonemap::ToneMapping};
 
 /// String formatted as PPM
 type PPMString = String;
 
-fn pixel_row_to_string(row: &[Color<f32>]) -> String {
+fn pixel_row_to_string(
+    row: &[Color<f32>],
+    tone_mapping: &ToneMapping,
+    encoding: Encoding,
+) -> String {
     let mut lines = vec![];
     let mut current_line = String::new();
     for pixel in row {
-        for p in pixel.as_rgb8().as_array().iter() {
+        for p in encoding
+            .to_rgb8(tone_mapping.map_color(*pixel))
+            .as_array()
+            .iter()
+        {
             let new_str = format!("{} ", p);
             // There is software out there that requires .ppm file lines to
             // be shorter than 70 characters. Thus, if the length _will_ be
@@ -28,35 +38,156 @@ fn pixel_row_to_string(row: &[Color<f32>]) -> String {
 }
 
 /// Transform the pixels to continuous string for PPM file.
-fn canvas_pixels_to_string(canvas: &Canvas) -> String {
+fn canvas_pixels_to_string(
+    canvas: &Canvas,
+    tone_mapping: &ToneMapping,
+    encoding: Encoding,
+) -> String {
     let mut pixels_string = String::new();
-    for row in canvas.pixels.chunks(canvas.height) {
-        let row_string = pixel_row_to_string(row);
+    for row in canvas.pixels.chunks(canvas.width.max(1)) {
+        let row_string = pixel_row_to_string(row, tone_mapping, encoding);
         pixels_string.push_str(format!("{}\n", row_string).as_str());
     }
     pixels_string.trim_end().to_string()
 }
 
 /// Reads canvas pixels and writes to PPM string.
+///
+/// Values are sRGB encoded, without tone mapping.
 pub fn ppm_from_canvas(canvas: &Canvas) -> PPMString {
+    ppm_from_canvas_with(
+        canvas,
+        &ToneMapping::default(),
+        Encoding::for_extension("ppm"),
+    )
+}
+
+/// Reads canvas pixels, tone maps and encodes them and writes to PPM string.
+///
+/// Use [`Encoding::Linear`] for values that are already display encoded.
+pub fn ppm_from_canvas_with(
+    canvas: &Canvas,
+    tone_mapping: &ToneMapping,
+    encoding: Encoding,
+) -> PPMString {
     format!(
         "\
         P3\n\
-        {height} {width}\n\
+        {width} {height}\n\
         255\n\
         {pixels}\n
         ",
-        height = canvas.height,
         width = canvas.width,
-        pixels = canvas_pixels_to_string(canvas)
+        height = canvas.height,
+        pixels = canvas_pixels_to_string(canvas, tone_mapping, encoding)
     )
 }
 
+/// Errors that can occur while parsing a PPM string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PpmError {
+    /// Magic number is not `P3`.
+    UnsupportedFormat(String),
+    /// Header ended before width, height and maximum value were read.
+    MissingHeader,
+    /// A token could not be parsed as a non-negative integer.
+    InvalidNumber(String),
+    /// Width and height are too large to address.
+    InvalidSize {
+        /// Width from the header
+        width: usize,
+        /// Height from the header
+        height: usize,
+    },
+    /// Number of color values does not match width and height.
+    PixelCount {
+        /// Values expected from the header
+        expected: usize,
+        /// Values found in the pixel data
+        found: usize,
+    },
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PpmError::UnsupportedFormat(magic) => write!(f, "unsupported PPM format {:?}", magic),
+            PpmError::MissingHeader => write!(f, "incomplete PPM header"),
+            PpmError::InvalidNumber(token) => write!(f, "invalid number {:?} in PPM", token),
+            PpmError::InvalidSize { width, height } => {
+                write!(f, "PPM size {}x{} is too large", width, height)
+            }
+            PpmError::PixelCount { expected, found } => write!(
+                f,
+                "expected {} color values in PPM, found {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PpmError {}
+
+/// Parse a plain (P3) PPM string into a canvas.
+///
+/// Width and height are read in PPM order and pixels row by row, starting at
+/// the top. Color values are scaled by the maximum value to [0, 1].
+pub fn canvas_from_ppm(ppm: &str) -> Result<Canvas, PpmError> {
+    // Comments run from '#' to the end of the line.
+    let mut tokens = ppm
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(str::split_whitespace);
+
+    let magic = tokens.next().ok_or(PpmError::MissingHeader)?;
+    if magic != "P3" {
+        return Err(PpmError::UnsupportedFormat(magic.to_string()));
+    }
+    let mut header = [0usize; 3];
+    for value in header.iter_mut() {
+        *value = parse_number(tokens.next().ok_or(PpmError::MissingHeader)?)?;
+    }
+    let [width, height, max_value] = header;
+    let scale = max_value.max(1) as f32;
+
+    let expected = width
+        .checked_mul(height)
+        .and_then(|count| count.checked_mul(3))
+        .ok_or(PpmError::InvalidSize { width, height })?;
+
+    let values = tokens
+        .map(|token| parse_number(token).map(|value| value as f32 / scale))
+        .collect::<Result<Vec<f32>, PpmError>>()?;
+    if values.len() != expected {
+        return Err(PpmError::PixelCount {
+            expected,
+            found: values.len(),
+        });
+    }
+
+    let mut canvas = Canvas::new(height, width);
+    for (pixel, rgb) in canvas.pixels.iter_mut().zip(values.chunks(3)) {
+        *pixel = Color::new(rgb[0], rgb[1], rgb[2]);
+    }
+    Ok(canvas)
+}
+
+fn parse_number(token: &str) -> Result<usize, PpmError> {
+    token
+        .parse()
+        .map_err(|_| PpmError::InvalidNumber(token.to_string()))
+}
+
 #[cfg(test)]
 mod test_ppm {
-    use crate::{canvas::Canvas, color::Color};
+    use crate::{
+        canvas::Canvas,
+        color::Color,
+        srgb::Encoding,
+        tonemap::{Operator, ToneMapping},
+    };
 
-    use super::{ppm_from_canvas, PPMString};
+    use super::{canvas_from_ppm, ppm_from_canvas, ppm_from_canvas_with, PPMString, PpmError};
 
     #[test]
     fn test_ppm_from_canvas_single_pixel() {
@@ -77,11 +208,13 @@ mod test_ppm {
         let result: PPMString = ppm_from_canvas(&canvas);
         let expected_result = "\
             P3\n\
-            5 3\n\
+            3 5\n\
             255\n\
-            0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 \n\
-            0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 \n\
-            0 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n
+            0 0 0 0 0 0 0 0 0 \n\
+            0 0 0 0 0 0 0 0 0 \n\
+            0 0 0 0 0 0 0 0 0 \n\
+            0 0 0 0 0 0 0 0 0 \n\
+            0 0 0 0 0 0 0 0 0\n
         ";
         assert!(result.eq(expected_result))
     }
@@ -98,29 +231,135 @@ mod test_ppm {
         let result: PPMString = ppm_from_canvas(&canvas);
         let expected_result = "\
             P3\n\
-            5 3\n\
+            3 5\n\
             255\n\
-            255 0 0 0 0 0 0 0 0 0 0 0 0 0 0 \n\
-            0 0 0 0 0 0 0 128 0 0 0 0 0 0 0 \n\
-            0 0 0 0 0 0 0 0 0 0 0 0 0 0 255\n
+            255 0 0 0 0 0 0 0 0 \n\
+            0 0 0 0 0 0 0 0 0 \n\
+            0 0 0 0 188 0 0 0 0 \n\
+            0 0 0 0 0 0 0 0 0 \n\
+            0 0 0 0 0 0 0 0 255\n
         ";
         assert!(result.eq(expected_result))
     }
 
     #[test]
     fn test_ppm_multiline() {
-        let mut canvas: Canvas = Canvas::new(10, 2);
+        let mut canvas: Canvas = Canvas::new(2, 10);
         canvas.fill(Color::new(1.0, 0.8, 0.6));
         let result: PPMString = ppm_from_canvas(&canvas);
         let expected_result = "\
             P3\n\
             10 2\n\
             255\n\
-            255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204 \n\
-            153 255 204 153 255 204 153 255 204 153 255 204 153 \n\
-            255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204 \n\
-            153 255 204 153 255 204 153 255 204 153 255 204 153\n
+            255 231 203 255 231 203 255 231 203 255 231 203 255 231 203 255 231 \n\
+            203 255 231 203 255 231 203 255 231 203 255 231 203 \n\
+            255 231 203 255 231 203 255 231 203 255 231 203 255 231 203 255 231 \n\
+            203 255 231 203 255 231 203 255 231 203 255 231 203\n
         ";
         assert!(result.eq(expected_result))
     }
+
+    #[test]
+    fn test_ppm_from_canvas_with_tone_mapping() {
+        let mut canvas: Canvas = Canvas::new(2, 1);
+        canvas[(0, 0)] = Color::new(1.0, 3.0, 0.0);
+        canvas[(1, 0)] = Color::new(0.5, 0.25, 0.125);
+        let tone_mapping = ToneMapping::new(1.0, Operator::Reinhard);
+        let result: PPMString = ppm_from_canvas_with(&canvas, &tone_mapping, Encoding::Linear);
+        let expected_result = "\
+            P3\n\
+            1 2\n\
+            255\n\
+            170 219 0 \n\
+            128 85 51\n
+        ";
+        assert!(result.eq(expected_result))
+    }
+
+    #[test]
+    fn test_ppm_from_canvas_with_srgb_encoding() {
+        let mut canvas: Canvas = Canvas::new(2, 1);
+        canvas.fill(Color::new(1.0, 0.8, 0.6));
+        canvas[(1, 0)] = Color::new(0.5, 0.18, 0.0);
+        let result: PPMString =
+            ppm_from_canvas_with(&canvas, &ToneMapping::default(), Encoding::Srgb);
+        let expected_result = "\
+            P3\n\
+            1 2\n\
+            255\n\
+            255 231 203 \n\
+            188 118 0\n
+        ";
+        assert!(result.eq(expected_result))
+    }
+
+    #[test]
+    fn test_canvas_from_ppm() {
+        let ppm = "\
+            P3\n\
+            4 3\n\
+            255\n\
+            255 127 0  0 127 255  127 255 0  255 255 255\n\
+            0 0 0  255 0 0  0 255 0  0 0 255\n\
+            255 255 0  0 255 255  255 0 255  127 127 127\n";
+        let canvas: Canvas = canvas_from_ppm(ppm).unwrap();
+        assert!(canvas.width == 4);
+        assert!(canvas.height == 3);
+        assert_relative_eq!(canvas[(0, 0)], Color::new(1.0, 0.49803922, 0.0));
+        assert_relative_eq!(canvas[(0, 1)], Color::new(0.0, 0.49803922, 1.0));
+        assert_relative_eq!(canvas[(1, 1)], Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(
+            canvas[(2, 3)],
+            Color::new(0.49803922, 0.49803922, 0.49803922)
+        );
+    }
+
+    #[test]
+    fn test_canvas_from_ppm_with_comments_and_max_value() {
+        let ppm = "\
+            P3\n\
+            # this is a comment\n\
+            1 1\n\
+            100 # and another\n\
+            100 50 25\n";
+        let canvas: Canvas = canvas_from_ppm(ppm).unwrap();
+        assert_relative_eq!(canvas[(0, 0)], Color::new(1.0, 0.5, 0.25));
+    }
+
+    #[test]
+    fn test_ppm_round_trip() {
+        let mut canvas: Canvas = Canvas::new(2, 3);
+        canvas[(0, 2)] = Color::new(1.0, 0.0, 0.0);
+        canvas[(1, 0)] = Color::new(0.0, 1.0, 0.0);
+        canvas[(1, 2)] = Color::new(0.0, 0.0, 1.0);
+        let ppm = ppm_from_canvas_with(&canvas, &ToneMapping::default(), Encoding::Linear);
+        let result: Canvas = canvas_from_ppm(&ppm).unwrap();
+        assert!(result.height == 2);
+        assert!(result.width == 3);
+        assert!(result.pixels == canvas.pixels);
+    }
+
+    #[test]
+    fn test_canvas_from_invalid_ppm() {
+        assert!(
+            canvas_from_ppm("P32\n1 1\n255\n0 0 0").err()
+                == Some(PpmError::UnsupportedFormat("P32".to_string()))
+        );
+        assert!(canvas_from_ppm("P3\n1").err() == Some(PpmError::MissingHeader));
+        assert!(
+            canvas_from_ppm("P3\n1 1\n255\n0 0").err()
+                == Some(PpmError::PixelCount {
+                    expected: 3,
+                    found: 2
+                })
+        );
+        let huge = format!("P3\n{} 2\n255\n0 0 0", usize::MAX);
+        assert!(
+            canvas_from_ppm(&huge).err()
+                == Some(PpmError::InvalidSize {
+                    width: usize::MAX,
+                    height: 2
+                })
+        );
+    }
 }
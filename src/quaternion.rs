@@ -0,0 +1,560 @@
+//! Quaternions for rotations.
+//!
+//! A unit [`Quat`] represents a rotation without the gimbal lock of Euler
+//! angles, composes by multiplication and interpolates smoothly with
+//! [`Quat::slerp`]. Conversions from and to axis-angle, Euler angles and
+//! rotation matrices are provided. Matrices are row-major and act on column
+//! vectors, and all rotations are right-handed.
+//!
+//! # Examples
+//! ```
+//! use std::f64::consts::FRAC_PI_2;
+//! use rust_raytracer::quaternion::Quat;
+//! use rust_raytracer::vectors::Vec3;
+//!
+//! let up = Vec3::<f64> { x: 0.0, y: 1.0, z: 0.0 };
+//! let quarter_turn = Quat::from_axis_angle(up, FRAC_PI_2);
+//! let rotated = quarter_turn.rotate(Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+//! assert!((rotated.z + 1.0).abs() < 1e-12);
+//! ```
+
+use num::traits::{Float, Num};
+use std::fmt;
+use std::ops::{Mul, Neg};
+
+use approx::{AbsDiffEq, RelativeEq, UlpsEq};
+
+use crate::vectors::Vec3;
+
+/// Quaternion `w + xi + yj + zk`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat<T: Num> {
+    /// Real part
+    pub w: T,
+    /// i-component
+    pub x: T,
+    /// j-component
+    pub y: T,
+    /// k-component
+    pub z: T,
+}
+
+/// Order in which rotations about the fixed x-, y- and z-axes are applied.
+///
+/// `Xyz` rotates about x first and z last, which is the same as intrinsic
+/// rotations about z, then the rotated y, then the rotated x.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EulerOrder {
+    /// x, then y, then z
+    Xyz,
+    /// x, then z, then y
+    Xzy,
+    /// y, then x, then z
+    Yxz,
+    /// y, then z, then x
+    Yzx,
+    /// z, then x, then y
+    Zxy,
+    /// z, then y, then x
+    Zyx,
+}
+
+impl EulerOrder {
+    /// Return axis indices in order of application.
+    fn axes(self) -> [usize; 3] {
+        match self {
+            EulerOrder::Xyz => [0, 1, 2],
+            EulerOrder::Xzy => [0, 2, 1],
+            EulerOrder::Yxz => [1, 0, 2],
+            EulerOrder::Yzx => [1, 2, 0],
+            EulerOrder::Zxy => [2, 0, 1],
+            EulerOrder::Zyx => [2, 1, 0],
+        }
+    }
+}
+
+fn component<T: Float>(vector: &Vec3<T>, axis: usize) -> T {
+    match axis {
+        0 => vector.x,
+        1 => vector.y,
+        _ => vector.z,
+    }
+}
+
+fn unit_axis<T: Float>(axis: usize) -> Vec3<T> {
+    let mut components = [T::zero(); 3];
+    components[axis] = T::one();
+    Vec3 {
+        x: components[0],
+        y: components[1],
+        z: components[2],
+    }
+}
+
+impl<T: Float> Quat<T> {
+    /// Convenience function for creating a new quaternion.
+    pub fn new(w: T, x: T, y: T, z: T) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// Return the rotation that does nothing.
+    pub fn identity() -> Self {
+        Self::new(T::one(), T::zero(), T::zero(), T::zero())
+    }
+
+    /// Return rotation by `angle` radians around `axis`, which need not be normalized.
+    pub fn from_axis_angle(axis: Vec3<T>, angle: T) -> Self {
+        let half = angle / (T::one() + T::one());
+        let axis = axis.normalize() * half.sin();
+        Self::new(half.cos(), axis.x, axis.y, axis.z)
+    }
+
+    /// Return normalized axis and angle in [0, 2π] of a unit quaternion.
+    ///
+    /// The identity gives the x-axis and angle zero.
+    pub fn to_axis_angle(&self) -> (Vec3<T>, T) {
+        let vector = Vec3 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+        };
+        let angle = (T::one() + T::one()) * vector.magnitude().atan2(self.w);
+        let axis = vector.try_normalize().unwrap_or_else(|| unit_axis(0));
+        (axis, angle)
+    }
+
+    /// Return rotation by the angles in `angles` around the fixed x-, y- and z-axes.
+    pub fn from_euler(angles: Vec3<T>, order: EulerOrder) -> Self {
+        order
+            .axes()
+            .iter()
+            .fold(Self::identity(), |rotation, &axis| {
+                Self::from_axis_angle(unit_axis(axis), component(&angles, axis)) * rotation
+            })
+    }
+
+    /// Return angles around the x-, y- and z-axes of a unit quaternion.
+    ///
+    /// The second angle in `order` lies in [-π/2, π/2]. At ±π/2, where the
+    /// first and last axis line up, the last angle is set to zero.
+    pub fn to_euler(&self, order: EulerOrder) -> Vec3<T> {
+        let m = self.to_matrix();
+        let [i, j, k] = order.axes();
+        // Sign of the permutation, negative for left-handed axis orders.
+        let s = if (j + 3 - i) % 3 == 1 {
+            T::one()
+        } else {
+            -T::one()
+        };
+        let sine = (-s * m[k][i]).max(-T::one()).min(T::one());
+        let middle = sine.asin();
+        let (first, last) = if sine.abs() < T::one() - T::epsilon().sqrt() {
+            ((s * m[k][j]).atan2(m[k][k]), (s * m[j][i]).atan2(m[i][i]))
+        } else {
+            ((-s * m[j][k]).atan2(m[j][j]), T::zero())
+        };
+        let mut angles = [T::zero(); 3];
+        angles[i] = first;
+        angles[j] = middle;
+        angles[k] = last;
+        Vec3 {
+            x: angles[0],
+            y: angles[1],
+            z: angles[2],
+        }
+    }
+
+    /// Return rotation matrix of a unit quaternion.
+    pub fn to_matrix(&self) -> [[T; 3]; 3] {
+        let two = T::one() + T::one();
+        let (w, x, y, z) = (self.w, self.x, self.y, self.z);
+        [
+            [
+                T::one() - two * (y * y + z * z),
+                two * (x * y - w * z),
+                two * (x * z + w * y),
+            ],
+            [
+                two * (x * y + w * z),
+                T::one() - two * (x * x + z * z),
+                two * (y * z - w * x),
+            ],
+            [
+                two * (x * z - w * y),
+                two * (y * z + w * x),
+                T::one() - two * (x * x + y * y),
+            ],
+        ]
+    }
+
+    /// Return unit quaternion of a rotation matrix.
+    ///
+    /// Uses Shepperd's method, which picks the largest of the four
+    /// components to divide by for numerical stability.
+    pub fn from_matrix(m: [[T; 3]; 3]) -> Self {
+        let one = T::one();
+        let quarter = one / (one + one + one + one);
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let quat = if trace > T::zero() {
+            let s = (trace + one).sqrt() * (one + one);
+            Self::new(
+                quarter * s,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (one + m[0][0] - m[1][1] - m[2][2]).sqrt() * (one + one);
+            Self::new(
+                (m[2][1] - m[1][2]) / s,
+                quarter * s,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = (one + m[1][1] - m[0][0] - m[2][2]).sqrt() * (one + one);
+            Self::new(
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                quarter * s,
+                (m[1][2] + m[2][1]) / s,
+            )
+        } else {
+            let s = (one + m[2][2] - m[0][0] - m[1][1]).sqrt() * (one + one);
+            Self::new(
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                quarter * s,
+            )
+        };
+        quat.normalize()
+    }
+
+    /// Compute dot product.
+    pub fn dot(&self, rhs: Self) -> T {
+        self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    /// Return quaternion magnitude.
+    pub fn magnitude(&self) -> T {
+        self.dot(*self).sqrt()
+    }
+
+    /// Return normalized quaternion.
+    pub fn normalize(&self) -> Self {
+        let mag = self.magnitude();
+        Self::new(self.w / mag, self.x / mag, self.y / mag, self.z / mag)
+    }
+
+    /// Return conjugate, which is the inverse rotation for unit quaternions.
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Return multiplicative inverse.
+    pub fn inverse(&self) -> Self {
+        let norm = self.dot(*self);
+        let conjugate = self.conjugate();
+        Self::new(
+            conjugate.w / norm,
+            conjugate.x / norm,
+            conjugate.y / norm,
+            conjugate.z / norm,
+        )
+    }
+
+    /// Rotate a vector by a unit quaternion.
+    pub fn rotate(&self, vector: Vec3<T>) -> Vec3<T> {
+        // v' = v + 2w (u × v) + 2 u × (u × v), with u the vector part.
+        let two = T::one() + T::one();
+        let u = Vec3 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+        };
+        let t = u.cross(&vector) * two;
+        vector + t * self.w + u.cross(&t)
+    }
+
+    /// Interpolate along the shorter arc at constant angular speed.
+    ///
+    /// `t = 0` gives self and `t = 1` other. Both must be unit quaternions.
+    pub fn slerp(&self, other: Self, t: T) -> Self {
+        let (other, cosine) = self.closest(other);
+        if cosine > T::one() - T::epsilon().sqrt() {
+            // Nearly parallel, where the sine below loses precision.
+            return self.nlerp(other, t);
+        }
+        let angle = cosine.acos();
+        let sine = angle.sin();
+        let a = ((T::one() - t) * angle).sin() / sine;
+        let b = (t * angle).sin() / sine;
+        Self::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+    }
+
+    /// Interpolate linearly along the shorter arc and normalize.
+    ///
+    /// Cheaper than [`slerp`](Quat::slerp), but the speed is not constant.
+    pub fn nlerp(&self, other: Self, t: T) -> Self {
+        let (other, _) = self.closest(other);
+        let s = T::one() - t;
+        Self::new(
+            s * self.w + t * other.w,
+            s * self.x + t * other.x,
+            s * self.y + t * other.y,
+            s * self.z + t * other.z,
+        )
+        .normalize()
+    }
+
+    /// Return the sign of other that is closest, and the dot product with it.
+    fn closest(&self, other: Self) -> (Self, T) {
+        let cosine = self.dot(other);
+        if cosine < T::zero() {
+            (-other, -cosine)
+        } else {
+            (other, cosine)
+        }
+    }
+}
+
+impl<T: fmt::Display + Num + fmt::Debug> fmt::Display for Quat<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({:?}, {:?}, {:?}, {:?})",
+            self.w, self.x, self.y, self.z
+        )
+    }
+}
+
+impl<T: AbsDiffEq + Num> AbsDiffEq for Quat<T>
+where
+    T::Epsilon: Copy,
+{
+    type Epsilon = T::Epsilon;
+
+    fn default_epsilon() -> T::Epsilon {
+        T::default_epsilon()
+    }
+
+    fn abs_diff_eq(&self, rhs: &Self, epsilon: T::Epsilon) -> bool {
+        T::abs_diff_eq(&self.w, &rhs.w, epsilon)
+            && T::abs_diff_eq(&self.x, &rhs.x, epsilon)
+            && T::abs_diff_eq(&self.y, &rhs.y, epsilon)
+            && T::abs_diff_eq(&self.z, &rhs.z, epsilon)
+    }
+}
+
+impl<T: RelativeEq + Num> RelativeEq for Quat<T>
+where
+    T::Epsilon: Copy,
+{
+    fn default_max_relative() -> T::Epsilon {
+        T::default_max_relative()
+    }
+
+    fn relative_eq(&self, rhs: &Self, epsilon: T::Epsilon, max_relative: T::Epsilon) -> bool {
+        T::relative_eq(&self.w, &rhs.w, epsilon, max_relative)
+            && T::relative_eq(&self.x, &rhs.x, epsilon, max_relative)
+            && T::relative_eq(&self.y, &rhs.y, epsilon, max_relative)
+            && T::relative_eq(&self.z, &rhs.z, epsilon, max_relative)
+    }
+}
+
+impl<T: UlpsEq + Num> UlpsEq for Quat<T>
+where
+    T::Epsilon: Copy,
+{
+    fn default_max_ulps() -> u32 {
+        T::default_max_ulps()
+    }
+
+    fn ulps_eq(&self, rhs: &Self, epsilon: T::Epsilon, max_ulps: u32) -> bool {
+        T::ulps_eq(&self.w, &rhs.w, epsilon, max_ulps)
+            && T::ulps_eq(&self.x, &rhs.x, epsilon, max_ulps)
+            && T::ulps_eq(&self.y, &rhs.y, epsilon, max_ulps)
+            && T::ulps_eq(&self.z, &rhs.z, epsilon, max_ulps)
+    }
+}
+
+/// Hamilton product, `a * b` rotates by `b` first and then by `a`.
+impl<T: Num + Copy> Mul<Quat<T>> for Quat<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        }
+    }
+}
+
+impl<T: Num + Neg<Output = T>> Neg for Quat<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            w: -self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_quaternion {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    use super::*;
+
+    fn vec3(x: f64, y: f64, z: f64) -> Vec3<f64> {
+        Vec3 { x, y, z }
+    }
+
+    fn rotate_with_matrix(m: [[f64; 3]; 3], v: Vec3<f64>) -> Vec3<f64> {
+        vec3(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    const ORDERS: [EulerOrder; 6] = [
+        EulerOrder::Xyz,
+        EulerOrder::Xzy,
+        EulerOrder::Yxz,
+        EulerOrder::Yzx,
+        EulerOrder::Zxy,
+        EulerOrder::Zyx,
+    ];
+
+    #[test]
+    fn rotate_vectors() {
+        let quarter = Quat::from_axis_angle(vec3(0.0, 0.0, 2.0), FRAC_PI_2);
+        assert_relative_eq!(
+            quarter.rotate(vec3(1.0, 0.0, 0.0)),
+            vec3(0.0, 1.0, 0.0),
+            epsilon = 1e-12
+        );
+        let identity = Quat::<f64>::identity();
+        assert_relative_eq!(identity.rotate(vec3(1.0, 2.0, 3.0)), vec3(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn compose_and_invert() {
+        let about_x = Quat::from_axis_angle(vec3(1.0, 0.0, 0.0), FRAC_PI_2);
+        let about_y = Quat::from_axis_angle(vec3(0.0, 1.0, 0.0), FRAC_PI_2);
+        let v = vec3(0.0, 1.0, 0.0);
+        // x turns y into z, then y turns z into x.
+        assert_relative_eq!(
+            (about_y * about_x).rotate(v),
+            vec3(1.0, 0.0, 0.0),
+            epsilon = 1e-12
+        );
+        assert_relative_eq!(
+            about_y.rotate(about_x.rotate(v)),
+            (about_y * about_x).rotate(v),
+            epsilon = 1e-12
+        );
+        let q = Quat::new(1.0, 2.0, -1.0, 0.5);
+        assert_relative_eq!(q * q.inverse(), Quat::identity(), epsilon = 1e-12);
+        let unit = q.normalize();
+        assert_relative_eq!(unit.inverse(), unit.conjugate(), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn axis_angle_round_trip() {
+        let axis = vec3(1.0, -2.0, 2.0);
+        let q = Quat::from_axis_angle(axis, 2.5);
+        let (result_axis, angle) = q.to_axis_angle();
+        assert_relative_eq!(result_axis, axis / 3.0, epsilon = 1e-12);
+        assert_relative_eq!(angle, 2.5, epsilon = 1e-12);
+        let (axis, angle) = Quat::<f64>::identity().to_axis_angle();
+        assert_relative_eq!(axis, vec3(1.0, 0.0, 0.0));
+        assert_relative_eq!(angle, 0.0);
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let inputs = [
+            Quat::from_axis_angle(vec3(1.0, 2.0, 3.0), 0.7),
+            Quat::from_axis_angle(vec3(1.0, 0.1, 0.0), PI),
+            Quat::from_axis_angle(vec3(0.0, 1.0, 0.1), 3.0),
+            Quat::from_axis_angle(vec3(0.1, 0.0, 1.0), -3.0),
+        ];
+        let v = vec3(0.3, -1.0, 2.0);
+        for q in inputs.iter() {
+            let m = q.to_matrix();
+            assert_relative_eq!(rotate_with_matrix(m, v), q.rotate(v), epsilon = 1e-12);
+            let back = Quat::from_matrix(m);
+            // q and -q are the same rotation.
+            let back = if back.dot(*q) < 0.0 { -back } else { back };
+            assert_relative_eq!(back, *q, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let angles = vec3(0.3, -0.5, 1.2);
+        for &order in ORDERS.iter() {
+            let q = Quat::from_euler(angles, order);
+            assert_relative_eq!(q.to_euler(order), angles, epsilon = 1e-10);
+        }
+        // Applied in order about the fixed axes.
+        let q = Quat::from_euler(vec3(FRAC_PI_2, FRAC_PI_2, 0.0), EulerOrder::Xyz);
+        assert_relative_eq!(
+            q.rotate(vec3(0.0, 1.0, 0.0)),
+            vec3(1.0, 0.0, 0.0),
+            epsilon = 1e-12
+        );
+        let q = Quat::from_euler(vec3(FRAC_PI_2, FRAC_PI_2, 0.0), EulerOrder::Yxz);
+        assert_relative_eq!(
+            q.rotate(vec3(0.0, 1.0, 0.0)),
+            vec3(0.0, 0.0, 1.0),
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn euler_gimbal_lock() {
+        for &order in ORDERS.iter() {
+            let mut angles = [0.4, 0.4, 0.4];
+            angles[order.axes()[1]] = FRAC_PI_2;
+            let q = Quat::from_euler(vec3(angles[0], angles[1], angles[2]), order);
+            let back = Quat::from_euler(q.to_euler(order), order);
+            let v = vec3(0.3, -1.0, 2.0);
+            assert_relative_eq!(back.rotate(v), q.rotate(v), epsilon = 1e-6);
+            assert_relative_eq!(component(&q.to_euler(order), order.axes()[2]), 0.0);
+        }
+    }
+
+    #[test]
+    fn interpolation() {
+        let start = Quat::<f64>::identity();
+        let end = Quat::from_axis_angle(vec3(0.0, 1.0, 0.0), 2.0);
+        let middle = start.slerp(end, 0.25);
+        let (axis, angle) = middle.to_axis_angle();
+        assert_relative_eq!(axis, vec3(0.0, 1.0, 0.0), epsilon = 1e-12);
+        assert_relative_eq!(angle, 0.5, epsilon = 1e-12);
+        assert_relative_eq!(start.slerp(end, 1.0), end, epsilon = 1e-12);
+
+        // Takes the short way even if the signs differ.
+        assert_relative_eq!(start.slerp(-end, 0.25), middle, epsilon = 1e-12);
+        let halfway = start.nlerp(-end, 0.5);
+        assert_relative_eq!(halfway, start.slerp(end, 0.5), epsilon = 1e-12);
+        assert_relative_eq!(halfway.magnitude(), 1.0, epsilon = 1e-12);
+        // nlerp is slower at the ends than slerp.
+        assert!(start.nlerp(end, 0.25).to_axis_angle().1 < 0.5);
+        assert_relative_eq!(start.slerp(start, 0.5), start);
+    }
+}
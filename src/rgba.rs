@@ -0,0 +1,329 @@
+//! Colors with an alpha channel and Porter-Duff compositing.
+//!
+//! A render over a transparent background leaves alpha 0 where nothing was
+//! hit, so it can be composited over other images afterwards. Colors are
+//! stored premultiplied by alpha, which is what the compositing operators
+//! work on and what keeps filtering at object edges correct.
+
+use std::ops::{Index, IndexMut};
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::srgb::Encoding;
+use crate::tonemap::ToneMapping;
+
+/// Color with alpha, premultiplied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba {
+    /// Color multiplied by alpha
+    pub color: Color<f32>,
+    /// Coverage in [0, 1], 0 is fully transparent
+    pub alpha: f32,
+}
+
+/// Porter-Duff compositing operator, combining a source with a destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Composite {
+    /// Source on top of destination.
+    Over,
+    /// Source where the destination is.
+    In,
+    /// Source where the destination is not.
+    Out,
+    /// Source on top of destination, only where the destination is.
+    Atop,
+    /// Source and destination where the other is not.
+    Xor,
+}
+
+impl Composite {
+    /// Return the fractions of source and destination that are kept.
+    fn factors(self, source_alpha: f32, destination_alpha: f32) -> (f32, f32) {
+        match self {
+            Composite::Over => (1.0, 1.0 - source_alpha),
+            Composite::In => (destination_alpha, 0.0),
+            Composite::Out => (1.0 - destination_alpha, 0.0),
+            Composite::Atop => (destination_alpha, 1.0 - source_alpha),
+            Composite::Xor => (1.0 - destination_alpha, 1.0 - source_alpha),
+        }
+    }
+}
+
+impl Rgba {
+    /// Fully transparent black.
+    pub const TRANSPARENT: Rgba = Rgba {
+        color: Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+        },
+        alpha: 0.0,
+    };
+
+    /// Fully opaque color.
+    pub fn opaque(color: Color<f32>) -> Self {
+        Self { color, alpha: 1.0 }
+    }
+
+    /// Construct from a color that is not multiplied by alpha yet.
+    pub fn from_straight(color: Color<f32>, alpha: f32) -> Self {
+        Self {
+            color: color * alpha,
+            alpha,
+        }
+    }
+
+    /// Return color divided by alpha, black where fully transparent.
+    pub fn to_straight(self) -> Color<f32> {
+        if self.alpha > 0.0 {
+            self.color * (1.0 / self.alpha)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    /// Composite self as source onto a destination.
+    pub fn composite(self, operator: Composite, destination: Rgba) -> Self {
+        let (source_factor, destination_factor) = operator.factors(self.alpha, destination.alpha);
+        Self {
+            color: self.color * source_factor + destination.color * destination_factor,
+            alpha: self.alpha * source_factor + destination.alpha * destination_factor,
+        }
+    }
+
+    /// Return copy with the straight color tone mapped and alpha kept.
+    pub fn tone_mapped(self, tone_mapping: &ToneMapping) -> Self {
+        Self::from_straight(tone_mapping.map_color(self.to_straight()), self.alpha)
+    }
+
+    /// Return straight 8-bit color and alpha, with color in the given encoding.
+    ///
+    /// Alpha is always stored linearly.
+    pub fn to_rgba8(self, encoding: Encoding) -> [u8; 4] {
+        let color = encoding.to_rgb8(self.to_straight());
+        let alpha = (self.alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [color.r, color.g, color.b, alpha]
+    }
+}
+
+/// Two dimensional grid of premultiplied RGBA pixels.
+pub struct RgbaCanvas {
+    /// Height
+    pub height: usize,
+    /// Width
+    pub width: usize,
+    /// Pixels
+    pub pixels: Vec<Rgba>,
+}
+
+impl RgbaCanvas {
+    /// Construct a new, fully transparent canvas.
+    pub fn new(height: usize, width: usize) -> Self {
+        Self {
+            height,
+            width,
+            pixels: vec![Rgba::TRANSPARENT; width * height],
+        }
+    }
+
+    /// Construct an opaque canvas from the pixels of a canvas.
+    pub fn from_canvas(canvas: &Canvas) -> Self {
+        Self {
+            height: canvas.height,
+            width: canvas.width,
+            pixels: canvas
+                .pixels
+                .iter()
+                .map(|&color| Rgba::opaque(color))
+                .collect(),
+        }
+    }
+
+    /// Construct from a color canvas and per pixel alpha, such as primary ray coverage.
+    ///
+    /// The colors are taken to be premultiplied already, which is the case when
+    /// misses contribute black.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is not one alpha value per pixel.
+    pub fn from_premultiplied(canvas: &Canvas, alpha: &[f32]) -> Self {
+        assert_eq!(canvas.pixels.len(), alpha.len(), "one alpha per pixel");
+        Self {
+            height: canvas.height,
+            width: canvas.width,
+            pixels: canvas
+                .pixels
+                .iter()
+                .zip(alpha.iter())
+                .map(|(&color, &alpha)| Rgba { color, alpha })
+                .collect(),
+        }
+    }
+
+    /// Get value at given row and column if index is in bounds.
+    pub fn get(&self, row: usize, column: usize) -> Option<&Rgba> {
+        self.get_index(row, column).map(|index| &self.pixels[index])
+    }
+
+    /// Get mutable value at given row and column if index is in bounds.
+    pub fn get_mut(&mut self, row: usize, column: usize) -> Option<&mut Rgba> {
+        self.get_index(row, column)
+            .map(move |index| &mut self.pixels[index])
+    }
+
+    fn get_index(&self, row: usize, column: usize) -> Option<usize> {
+        if row < self.height && column < self.width {
+            Some(row * self.width + column)
+        } else {
+            None
+        }
+    }
+
+    /// Composite self as source onto a destination canvas of the same size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the canvases differ in size.
+    pub fn composite(&self, operator: Composite, destination: &RgbaCanvas) -> RgbaCanvas {
+        assert!(
+            self.height == destination.height && self.width == destination.width,
+            "canvases differ in size"
+        );
+        RgbaCanvas {
+            height: self.height,
+            width: self.width,
+            pixels: self
+                .pixels
+                .iter()
+                .zip(destination.pixels.iter())
+                .map(|(source, destination)| source.composite(operator, *destination))
+                .collect(),
+        }
+    }
+
+    /// Return an opaque canvas with self composited over a background color.
+    pub fn flatten(&self, background: Color<f32>) -> Canvas {
+        let mut canvas = Canvas::new(self.height, self.width);
+        let background = Rgba::opaque(background);
+        for (target, source) in canvas.pixels.iter_mut().zip(self.pixels.iter()) {
+            *target = source.composite(Composite::Over, background).color;
+        }
+        canvas
+    }
+}
+
+impl Index<(usize, usize)> for RgbaCanvas {
+    type Output = Rgba;
+
+    fn index(&self, indices: (usize, usize)) -> &Self::Output {
+        let (row, column) = indices;
+        self.get(row, column).unwrap()
+    }
+}
+
+impl IndexMut<(usize, usize)> for RgbaCanvas {
+    fn index_mut(&mut self, indices: (usize, usize)) -> &mut Self::Output {
+        let (row, column) = indices;
+        self.get_mut(row, column).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test_rgba {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::srgb::Encoding;
+
+    use super::*;
+
+    fn red(alpha: f32) -> Rgba {
+        Rgba::from_straight(Color::new(1.0, 0.0, 0.0), alpha)
+    }
+
+    fn blue(alpha: f32) -> Rgba {
+        Rgba::from_straight(Color::new(0.0, 0.0, 1.0), alpha)
+    }
+
+    #[test]
+    fn straight_and_premultiplied() {
+        let color = Rgba::from_straight(Color::new(0.8, 0.4, 0.2), 0.5);
+        assert_relative_eq!(color.color, Color::new(0.4, 0.2, 0.1));
+        assert_relative_eq!(color.to_straight(), Color::new(0.8, 0.4, 0.2));
+        assert_relative_eq!(Rgba::TRANSPARENT.to_straight(), Color::new(0.0, 0.0, 0.0));
+        assert!(color.to_rgba8(Encoding::Linear) == [204, 102, 51, 128]);
+    }
+
+    #[test]
+    fn tone_mapping_keeps_alpha() {
+        let tone_mapping = ToneMapping::new(0.0, crate::tonemap::Operator::Reinhard);
+        let color = Rgba::from_straight(Color::new(1.0, 3.0, 0.0), 0.5).tone_mapped(&tone_mapping);
+        assert_relative_eq!(color.alpha, 0.5);
+        assert_relative_eq!(color.to_straight(), Color::new(0.5, 0.75, 0.0));
+        assert_eq!(
+            Rgba::TRANSPARENT.tone_mapped(&tone_mapping),
+            Rgba::TRANSPARENT
+        );
+    }
+
+    #[test]
+    fn porter_duff_operators() {
+        let over = red(0.5).composite(Composite::Over, blue(1.0));
+        assert_relative_eq!(over.color, Color::new(0.5, 0.0, 0.5));
+        assert_relative_eq!(over.alpha, 1.0);
+
+        let over = red(0.5).composite(Composite::Over, blue(0.5));
+        assert_relative_eq!(over.color, Color::new(0.5, 0.0, 0.25));
+        assert_relative_eq!(over.alpha, 0.75);
+
+        let inside = red(1.0).composite(Composite::In, blue(0.25));
+        assert_relative_eq!(inside.color, Color::new(0.25, 0.0, 0.0));
+        assert_relative_eq!(inside.alpha, 0.25);
+
+        let out = red(1.0).composite(Composite::Out, blue(0.25));
+        assert_relative_eq!(out.color, Color::new(0.75, 0.0, 0.0));
+        assert_relative_eq!(out.alpha, 0.75);
+
+        let atop = red(0.5).composite(Composite::Atop, blue(0.5));
+        assert_relative_eq!(atop.color, Color::new(0.25, 0.0, 0.25));
+        assert_relative_eq!(atop.alpha, 0.5);
+
+        let xor = red(1.0).composite(Composite::Xor, blue(1.0));
+        assert_relative_eq!(xor.alpha, 0.0);
+    }
+
+    #[test]
+    fn transparent_source_leaves_destination() {
+        for operator in [Composite::Over, Composite::Atop].iter() {
+            let result = Rgba::TRANSPARENT.composite(*operator, blue(0.7));
+            assert_relative_eq!(result.color, blue(0.7).color);
+            assert_relative_eq!(result.alpha, 0.7);
+        }
+    }
+
+    #[test]
+    fn composite_and_flatten_canvases() {
+        let mut source = RgbaCanvas::new(1, 2);
+        source[(0, 0)] = red(1.0);
+        let mut background = Canvas::new(1, 2);
+        background.fill(Color::new(0.0, 0.0, 1.0));
+        let destination = RgbaCanvas::from_canvas(&background);
+
+        let result = source.composite(Composite::Over, &destination);
+        assert!(result[(0, 0)] == red(1.0));
+        assert!(result[(0, 1)] == blue(1.0));
+
+        let flat = source.flatten(Color::new(0.0, 1.0, 0.0));
+        assert_relative_eq!(flat[(0, 0)], Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(flat[(0, 1)], Color::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn from_render_coverage() {
+        let mut render = Canvas::new(1, 2);
+        render[(0, 0)] = Color::new(0.5, 0.5, 0.5);
+        let canvas = RgbaCanvas::from_premultiplied(&render, &[1.0, 0.0]);
+        assert!(canvas[(0, 0)] == Rgba::opaque(Color::new(0.5, 0.5, 0.5)));
+        assert!(canvas[(0, 1)] == Rgba::TRANSPARENT);
+    }
+}
@@ -0,0 +1,91 @@
+//! Small seedable random number generator.
+//!
+//! Renders that use random sampling must be reproducible for golden tests, so
+//! all sampling draws from an explicitly seeded generator.
+
+/// PCG32 generator (XSH RR variant) by Melissa O'Neill.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+impl Pcg32 {
+    /// Construct a new generator from a seed, using the default stream.
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, 0xda3e39cb94b95bdb)
+    }
+
+    /// Construct a new generator from a seed and stream.
+    ///
+    /// Different streams give independent sequences for the same seed, for
+    /// example one per pixel or thread.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// Return next 32-bit value.
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    /// Return next value uniformly distributed in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        // Use the upper 24 bits, which fit the f32 mantissa exactly.
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod test_rng {
+    use super::Pcg32;
+
+    #[test]
+    fn matches_reference_sequence() {
+        // First outputs of the PCG reference demo, seeded with 42 on stream 54.
+        let mut rng = Pcg32::with_stream(42, 54);
+        let expected = [
+            0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+        ];
+        for value in expected.iter() {
+            assert_eq!(rng.next_u32(), *value);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        let mut rng1 = Pcg32::new(7);
+        let mut rng2 = Pcg32::new(7);
+        let mut rng3 = Pcg32::new(8);
+        let sequence1: Vec<u32> = (0..8).map(|_| rng1.next_u32()).collect();
+        let sequence2: Vec<u32> = (0..8).map(|_| rng2.next_u32()).collect();
+        let sequence3: Vec<u32> = (0..8).map(|_| rng3.next_u32()).collect();
+        assert!(sequence1 == sequence2);
+        assert!(sequence1 != sequence3);
+    }
+
+    #[test]
+    fn floats_in_unit_interval() {
+        let mut rng = Pcg32::new(1);
+        let mut sum = 0.0;
+        for _ in 0..10000 {
+            let value = rng.next_f32();
+            assert!((0.0..1.0).contains(&value));
+            sum += value;
+        }
+        assert_relative_eq!(sum / 10000.0, 0.5, epsilon = 0.01);
+    }
+}
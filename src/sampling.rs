@@ -0,0 +1,215 @@
+//! Warping of uniform random numbers to common sampling domains.
+//!
+//! Every function maps `u1` and `u2` in [0, 1) to a sample and returns its
+//! probability density, with respect to solid angle for directions and to
+//! area for points. Directions around a normal are sampled in a local frame
+//! with z up, and moved to world space with [`Onb`](crate::vectors::Onb).
+
+use std::f32::consts::{FRAC_1_PI, FRAC_PI_2, FRAC_PI_4, PI};
+
+use crate::vectors::Vec3;
+
+fn direction(cos_theta: f32, phi: f32) -> Vec3<f32> {
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    Vec3 {
+        x: sin_theta * phi.cos(),
+        y: sin_theta * phi.sin(),
+        z: cos_theta,
+    }
+}
+
+/// Map the unit square to the unit disk, preserving stratification.
+pub fn concentric_disk(u1: f32, u2: f32) -> ((f32, f32), f32) {
+    let (x, y) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return ((0.0, 0.0), FRAC_1_PI);
+    }
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    ((radius * theta.cos(), radius * theta.sin()), FRAC_1_PI)
+}
+
+/// Sample a direction uniformly over the unit sphere.
+pub fn uniform_sphere(u1: f32, u2: f32) -> (Vec3<f32>, f32) {
+    (
+        direction(1.0 - 2.0 * u1, 2.0 * PI * u2),
+        uniform_sphere_pdf(),
+    )
+}
+
+/// Density of [`uniform_sphere`].
+pub fn uniform_sphere_pdf() -> f32 {
+    0.25 * FRAC_1_PI
+}
+
+/// Sample a direction uniformly over the hemisphere around z.
+pub fn uniform_hemisphere(u1: f32, u2: f32) -> (Vec3<f32>, f32) {
+    (direction(u1, 2.0 * PI * u2), uniform_hemisphere_pdf())
+}
+
+/// Density of [`uniform_hemisphere`].
+pub fn uniform_hemisphere_pdf() -> f32 {
+    0.5 * FRAC_1_PI
+}
+
+/// Sample a direction around z with density proportional to its cosine.
+///
+/// Uses Malley's method, projecting a disk sample up to the hemisphere.
+pub fn cosine_hemisphere(u1: f32, u2: f32) -> (Vec3<f32>, f32) {
+    let ((x, y), _) = concentric_disk(u1, u2);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    (Vec3 { x, y, z }, cosine_hemisphere_pdf(z))
+}
+
+/// Density of [`cosine_hemisphere`] for a direction with the given cosine to z.
+pub fn cosine_hemisphere_pdf(cos_theta: f32) -> f32 {
+    cos_theta.max(0.0) * FRAC_1_PI
+}
+
+/// Sample a direction uniformly within a cone around z.
+///
+/// `cos_theta_max` is the cosine of the half angle of the cone.
+pub fn uniform_cone(u1: f32, u2: f32, cos_theta_max: f32) -> (Vec3<f32>, f32) {
+    let cos_theta = 1.0 - u1 * (1.0 - cos_theta_max);
+    (
+        direction(cos_theta, 2.0 * PI * u2),
+        uniform_cone_pdf(cos_theta_max),
+    )
+}
+
+/// Density of [`uniform_cone`].
+pub fn uniform_cone_pdf(cos_theta_max: f32) -> f32 {
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+}
+
+/// Sample a point uniformly on the triangle with corners `a`, `b` and `c`.
+pub fn uniform_triangle(
+    u1: f32,
+    u2: f32,
+    a: Vec3<f32>,
+    b: Vec3<f32>,
+    c: Vec3<f32>,
+) -> (Vec3<f32>, f32) {
+    let root = u1.sqrt();
+    let (b0, b1) = (1.0 - root, u2 * root);
+    let point = a * b0 + b * b1 + c * (1.0 - b0 - b1);
+    let area = 0.5 * (b - a).cross(&(c - a)).magnitude();
+    (point, 1.0 / area)
+}
+
+#[cfg(test)]
+mod test_sampling {
+    use crate::rng::Pcg32;
+    use crate::vectors::Vec3;
+
+    use super::*;
+
+    const SAMPLES: usize = 20000;
+
+    fn vector(x: f32, y: f32, z: f32) -> Vec3<f32> {
+        Vec3 { x, y, z }
+    }
+
+    /// Return mean of a function of the samples and mean of the inverse density.
+    fn moments<S, F>(sample: S, function: F) -> (f32, f32)
+    where
+        S: Fn(f32, f32) -> (Vec3<f32>, f32),
+        F: Fn(Vec3<f32>) -> f32,
+    {
+        let mut rng = Pcg32::new(11);
+        let (mut sum, mut measure) = (0.0_f64, 0.0_f64);
+        for _ in 0..SAMPLES {
+            let (value, pdf) = sample(rng.next_f32(), rng.next_f32());
+            sum += function(value) as f64;
+            measure += 1.0 / pdf as f64;
+        }
+        let count = SAMPLES as f64;
+        ((sum / count) as f32, (measure / count) as f32)
+    }
+
+    #[test]
+    fn disk_samples_cover_unit_disk() {
+        let mut rng = Pcg32::new(3);
+        let (mut sum, mut radius_squared) = ((0.0, 0.0), 0.0);
+        for _ in 0..SAMPLES {
+            let ((x, y), pdf) = concentric_disk(rng.next_f32(), rng.next_f32());
+            assert!(x * x + y * y <= 1.0 + 1e-6);
+            assert_relative_eq!(pdf, 1.0 / PI);
+            sum = (sum.0 + x, sum.1 + y);
+            radius_squared += x * x + y * y;
+        }
+        assert_relative_eq!(sum.0 / SAMPLES as f32, 0.0, epsilon = 0.02);
+        assert_relative_eq!(sum.1 / SAMPLES as f32, 0.0, epsilon = 0.02);
+        assert_relative_eq!(radius_squared / SAMPLES as f32, 0.5, epsilon = 0.01);
+        assert_eq!(concentric_disk(0.5, 0.5).0, (0.0, 0.0));
+        assert_relative_eq!((concentric_disk(1.0, 0.5).0).0, 1.0);
+    }
+
+    #[test]
+    fn sphere_moments() {
+        let (mean_z, area) = moments(uniform_sphere, |v| v.z);
+        assert_relative_eq!(mean_z, 0.0, epsilon = 0.02);
+        assert_relative_eq!(area, 4.0 * PI, epsilon = 1e-4);
+        let (mean_z2, _) = moments(uniform_sphere, |v| v.z * v.z);
+        assert_relative_eq!(mean_z2, 1.0 / 3.0, epsilon = 0.01);
+        let (length, _) = moments(uniform_sphere, |v| v.magnitude());
+        assert_relative_eq!(length, 1.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn hemisphere_moments() {
+        let (mean_z, area) = moments(uniform_hemisphere, |v| v.z);
+        assert_relative_eq!(mean_z, 0.5, epsilon = 0.01);
+        assert_relative_eq!(area, 2.0 * PI, epsilon = 1e-4);
+        let (mean_x, _) = moments(uniform_hemisphere, |v| v.x);
+        assert_relative_eq!(mean_x, 0.0, epsilon = 0.02);
+    }
+
+    #[test]
+    fn cosine_hemisphere_moments() {
+        let (mean_z, _) = moments(cosine_hemisphere, |v| v.z);
+        assert_relative_eq!(mean_z, 2.0 / 3.0, epsilon = 0.01);
+        // Estimates the integral of the cosine over the hemisphere, which is π.
+        let (integral, _) = moments(cosine_hemisphere, |v| v.z / cosine_hemisphere_pdf(v.z));
+        assert_relative_eq!(integral, PI, epsilon = 1e-3);
+        let (_, pdf) = cosine_hemisphere(0.5, 0.5);
+        assert_relative_eq!(pdf, 1.0 / PI);
+    }
+
+    #[test]
+    fn cone_moments() {
+        let cos_theta_max = 0.8;
+        let cone = |u1, u2| uniform_cone(u1, u2, cos_theta_max);
+        let (mean_z, solid_angle) = moments(cone, |v| v.z);
+        assert_relative_eq!(mean_z, 0.9, epsilon = 0.005);
+        assert_relative_eq!(solid_angle, 2.0 * PI * 0.2, epsilon = 1e-4);
+        let (minimum, _) = moments(cone, |v| if v.z < cos_theta_max - 1e-6 { 1.0 } else { 0.0 });
+        assert_relative_eq!(minimum, 0.0);
+    }
+
+    #[test]
+    fn triangle_moments() {
+        let (a, b, c) = (
+            vector(0.0, 0.0, 1.0),
+            vector(3.0, 0.0, 1.0),
+            vector(0.0, 6.0, 1.0),
+        );
+        let triangle = |u1, u2| uniform_triangle(u1, u2, a, b, c);
+        let (mean_x, area) = moments(triangle, |p| p.x);
+        let (mean_y, _) = moments(triangle, |p| p.y);
+        let (outside, _) = moments(triangle, |p| {
+            if p.x < 0.0 || p.y < 0.0 || 2.0 * p.x + p.y > 6.0 + 1e-5 || p.z != 1.0 {
+                1.0
+            } else {
+                0.0
+            }
+        });
+        assert_relative_eq!(area, 9.0, epsilon = 1e-4);
+        assert_relative_eq!(mean_x, 1.0, epsilon = 0.02);
+        assert_relative_eq!(mean_y, 2.0, epsilon = 0.04);
+        assert_relative_eq!(outside, 0.0);
+    }
+}
@@ -0,0 +1,194 @@
+//! Checks of radiance samples before they are accumulated into pixels.
+//!
+//! A single NaN or infinite sample, for example from normalizing a zero
+//! length vector, ruins the whole pixel and silently turns black in 8-bit
+//! output. [`SampleChecker`] replaces such samples with black, counts them
+//! and, in debug mode, records the pixel and path vertices that produced
+//! them. It can also clamp bright samples, which removes fireflies at the
+//! cost of some energy.
+
+use std::fmt;
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::vectors::Vec3;
+
+/// Non-finite sample recorded in debug mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NonFiniteSample {
+    /// Row of the pixel
+    pub row: usize,
+    /// Column of the pixel
+    pub column: usize,
+    /// Index of the sample within the pixel
+    pub sample: usize,
+    /// Radiance that was rejected
+    pub color: Color<f32>,
+    /// Vertices of the ray path, starting at the camera
+    pub path: Vec<Vec3<f32>>,
+}
+
+impl fmt::Display for NonFiniteSample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "non-finite radiance {} at pixel ({}, {}) sample {}",
+            self.color, self.row, self.column, self.sample
+        )?;
+        for (bounce, vertex) in self.path.iter().enumerate() {
+            write!(f, "\n  {}: {}", bounce, vertex)?;
+        }
+        Ok(())
+    }
+}
+
+/// Scale a color down so no channel is above `maximum`, keeping its hue.
+pub fn clamp_radiance(color: Color<f32>, maximum: f32) -> Color<f32> {
+    let largest = color.r.max(color.g).max(color.b);
+    if largest > maximum {
+        color * (maximum / largest)
+    } else {
+        color
+    }
+}
+
+/// Return row and column of every pixel with a NaN or infinite channel.
+pub fn non_finite_pixels(canvas: &Canvas) -> Vec<(usize, usize)> {
+    canvas
+        .pixels
+        .iter()
+        .enumerate()
+        .filter(|(_, pixel)| !pixel.is_finite())
+        .map(|(index, _)| (index / canvas.width, index % canvas.width))
+        .collect()
+}
+
+/// Filter for radiance samples, with optional clamping and debug records.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SampleChecker {
+    /// Largest channel value of a sample, unlimited if `None`
+    pub clamp: Option<f32>,
+    /// Whether to record every non-finite sample
+    pub debug: bool,
+    /// Number of non-finite samples seen
+    pub non_finite_count: usize,
+    /// Number of samples that were clamped
+    pub clamped_count: usize,
+    /// Non-finite samples recorded in debug mode
+    pub records: Vec<NonFiniteSample>,
+}
+
+impl SampleChecker {
+    /// Construct a checker that only rejects non-finite samples.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set largest channel value of a sample.
+    pub fn with_clamp(mut self, maximum: f32) -> Self {
+        self.clamp = Some(maximum);
+        self
+    }
+
+    /// Set whether to record every non-finite sample.
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Return the sample to accumulate for a pixel.
+    ///
+    /// Non-finite samples become black, and `path` is only called to
+    /// collect the path vertices when such a sample is recorded.
+    pub fn check<F>(
+        &mut self,
+        row: usize,
+        column: usize,
+        sample: usize,
+        color: Color<f32>,
+        path: F,
+    ) -> Color<f32>
+    where
+        F: FnOnce() -> Vec<Vec3<f32>>,
+    {
+        if !color.is_finite() {
+            self.non_finite_count += 1;
+            if self.debug {
+                self.records.push(NonFiniteSample {
+                    row,
+                    column,
+                    sample,
+                    color,
+                    path: path(),
+                });
+            }
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        match self.clamp {
+            Some(maximum) if color.r.max(color.g).max(color.b) > maximum => {
+                self.clamped_count += 1;
+                clamp_radiance(color, maximum)
+            }
+            _ => color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_sanitize {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::vectors::Vec3;
+
+    use super::*;
+
+    fn point(x: f32, y: f32, z: f32) -> Vec3<f32> {
+        Vec3 { x, y, z }
+    }
+
+    #[test]
+    fn clamping_keeps_hue() {
+        let clamped = clamp_radiance(Color::new(40.0, 20.0, 10.0), 4.0);
+        assert_relative_eq!(clamped, Color::new(4.0, 2.0, 1.0));
+        let dim = Color::new(0.5, 0.2, 0.1);
+        assert_relative_eq!(clamp_radiance(dim, 4.0), dim);
+    }
+
+    #[test]
+    fn non_finite_samples_become_black() {
+        let mut checker = SampleChecker::new().with_clamp(10.0);
+        let bad = Color::new(f32::NAN, 1.0, 1.0);
+        let result = checker.check(0, 0, 0, bad, || panic!("path not needed"));
+        assert_relative_eq!(result, Color::new(0.0, 0.0, 0.0));
+        let result = checker.check(0, 0, 1, Color::new(0.0, 100.0, 0.0), Vec::new);
+        assert_relative_eq!(result, Color::new(0.0, 10.0, 0.0));
+        let result = checker.check(0, 0, 2, Color::new(0.0, 1.0, 0.0), Vec::new);
+        assert_relative_eq!(result, Color::new(0.0, 1.0, 0.0));
+        assert_eq!((checker.non_finite_count, checker.clamped_count), (1, 1));
+        assert!(checker.records.is_empty());
+    }
+
+    #[test]
+    fn debug_records_path() {
+        let mut checker = SampleChecker::new().with_debug(true);
+        let direction = point(0.0, 0.0, 0.0).normalize();
+        let color = Color::new(1.0, 1.0, 1.0) * direction.x;
+        let path = vec![point(0.0, 0.0, -5.0), point(0.0, 0.0, -1.0)];
+        checker.check(3, 7, 2, color, || path.clone());
+        assert_eq!(checker.records.len(), 1);
+        let record = &checker.records[0];
+        assert_eq!((record.row, record.column, record.sample), (3, 7, 2));
+        assert!(record.path == path);
+        let report = record.to_string();
+        assert!(report.starts_with("non-finite radiance"));
+        assert!(report.contains("pixel (3, 7) sample 2\n  0: "));
+    }
+
+    #[test]
+    fn find_non_finite_pixels() {
+        let mut canvas = Canvas::new(2, 3);
+        canvas[(1, 2)] = Color::new(0.0, f32::INFINITY, 0.0);
+        canvas[(0, 1)] = Color::new(0.0, 0.0, f32::NAN);
+        assert!(non_finite_pixels(&canvas) == vec![(0, 1), (1, 2)]);
+    }
+}
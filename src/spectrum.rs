@@ -0,0 +1,399 @@
+//! Spectral rendering with hero wavelength sampling.
+//!
+//! Instead of RGB triples, a spectral render carries radiance at a few
+//! wavelengths per path, chosen by [`SampledWavelengths::sample_uniform`].
+//! RGB material colors are uplifted to smooth spectra with [`RgbSpectrum`],
+//! glass gets a wavelength dependent index of refraction from [`Ior`], and
+//! [`SampledSpectrum::to_rgb`] integrates the result back into a linear sRGB
+//! color for the canvas.
+//!
+//! Only available with the `spectral` cargo feature.
+
+use std::ops::{Add, AddAssign, Div, Mul};
+
+use crate::cie::{x_bar, y_bar, z_bar, LAMBDA_MAX, LAMBDA_MIN, Y_INTEGRAL};
+use crate::color::Color;
+use crate::colorspace::{Xyz, D65, SRGB};
+
+/// Number of wavelengths carried by every path.
+pub const SAMPLES: usize = 4;
+
+/// Wavelengths in nanometers carried by a path, with their probability densities.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledWavelengths {
+    /// Wavelengths in nanometers
+    pub lambda: [f32; SAMPLES],
+    /// Probability density of each wavelength
+    pub pdf: [f32; SAMPLES],
+}
+
+impl SampledWavelengths {
+    /// Sample a hero wavelength uniformly and space the others evenly after it.
+    ///
+    /// Wavelengths that fall past the end of the range wrap around to the start.
+    pub fn sample_uniform(u: f32) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let mut lambda = [hero; SAMPLES];
+        for (i, value) in lambda.iter_mut().enumerate().skip(1) {
+            *value = hero + i as f32 * range / SAMPLES as f32;
+            if *value > LAMBDA_MAX {
+                *value -= range;
+            }
+        }
+        Self {
+            lambda,
+            pdf: [1.0 / range; SAMPLES],
+        }
+    }
+
+    /// Drop all but the hero wavelength.
+    ///
+    /// Used when a path goes through a dispersive interface, since every
+    /// wavelength would refract in a different direction.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for pdf in self.pdf.iter_mut().skip(1) {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= SAMPLES as f32;
+    }
+
+    /// Return whether only the hero wavelength is left.
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf.iter().skip(1).all(|&pdf| pdf == 0.0)
+    }
+}
+
+/// Spectral quantity at the wavelengths of a [`SampledWavelengths`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledSpectrum(pub [f32; SAMPLES]);
+
+impl SampledSpectrum {
+    /// Spectrum with the same value at all wavelengths.
+    pub fn constant(value: f32) -> Self {
+        Self([value; SAMPLES])
+    }
+
+    /// Convert to XYZ with Monte Carlo integration over the sampled wavelengths.
+    ///
+    /// Scaled so that a constant spectrum of 1.0 gives luminance 1.0.
+    pub fn to_xyz(&self, wavelengths: &SampledWavelengths) -> Xyz {
+        let mut xyz = Xyz {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        for i in 0..SAMPLES {
+            let pdf = wavelengths.pdf[i];
+            if pdf == 0.0 {
+                continue;
+            }
+            let lambda = wavelengths.lambda[i];
+            let weight = self.0[i] / pdf;
+            xyz.x += x_bar(lambda) * weight;
+            xyz.y += y_bar(lambda) * weight;
+            xyz.z += z_bar(lambda) * weight;
+        }
+        let scale = 1.0 / (SAMPLES as f32 * Y_INTEGRAL);
+        Xyz {
+            x: xyz.x * scale,
+            y: xyz.y * scale,
+            z: xyz.z * scale,
+        }
+    }
+
+    /// Convert to a linear sRGB color.
+    ///
+    /// Uplifted spectra are relative to an equal energy white, which is adapted
+    /// to the D65 white of sRGB.
+    pub fn to_rgb(&self, wavelengths: &SampledWavelengths) -> Color<f32> {
+        let equal_energy = Xyz::white((1.0 / 3.0, 1.0 / 3.0));
+        let xyz = self
+            .to_xyz(wavelengths)
+            .adapt(equal_energy, Xyz::white(D65));
+        SRGB.from_xyz(xyz)
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        let mut result = self;
+        result += rhs;
+        result
+    }
+}
+
+impl AddAssign for SampledSpectrum {
+    fn add_assign(&mut self, rhs: Self) {
+        for (value, other) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *value += other;
+        }
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let mut result = self;
+        for (value, other) in result.0.iter_mut().zip(rhs.0.iter()) {
+            *value *= other;
+        }
+        result
+    }
+}
+
+impl Mul<f32> for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self(self.0.map(|value| value * rhs))
+    }
+}
+
+impl Div<f32> for SampledSpectrum {
+    type Output = Self;
+
+    fn div(self, rhs: f32) -> Self {
+        Self(self.0.map(|value| value / rhs))
+    }
+}
+
+/// Smooth reflectance spectrum for an RGB color, after Brian Smits (1999).
+///
+/// The spectrum is stored in ten bins covering 380 to 720 nm. It is built
+/// from the spectra of white, the primaries and the secondaries, so that
+/// colors within [0, 1] give reflectances within [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RgbSpectrum {
+    bins: [f32; 10],
+}
+
+const BIN_MIN: f32 = 380.0;
+const BIN_MAX: f32 = 720.0;
+
+const SMITS_WHITE: [f32; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f32; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f32; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f32; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f32; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f32; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f32; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+impl RgbSpectrum {
+    /// Uplift a linear RGB color.
+    pub fn from_rgb(color: Color<f32>) -> Self {
+        let (r, g, b) = (color.r, color.g, color.b);
+        let mut bins = [0.0; 10];
+        let mut add = |weight: f32, basis: &[f32; 10]| {
+            for (bin, value) in bins.iter_mut().zip(basis.iter()) {
+                *bin += weight * value;
+            }
+        };
+        if r <= g && r <= b {
+            add(r, &SMITS_WHITE);
+            if g <= b {
+                add(g - r, &SMITS_CYAN);
+                add(b - g, &SMITS_BLUE);
+            } else {
+                add(b - r, &SMITS_CYAN);
+                add(g - b, &SMITS_GREEN);
+            }
+        } else if g <= r && g <= b {
+            add(g, &SMITS_WHITE);
+            if r <= b {
+                add(r - g, &SMITS_MAGENTA);
+                add(b - r, &SMITS_BLUE);
+            } else {
+                add(b - g, &SMITS_MAGENTA);
+                add(r - b, &SMITS_RED);
+            }
+        } else {
+            add(b, &SMITS_WHITE);
+            if r <= g {
+                add(r - b, &SMITS_YELLOW);
+                add(g - r, &SMITS_GREEN);
+            } else {
+                add(g - b, &SMITS_YELLOW);
+                add(r - g, &SMITS_RED);
+            }
+        }
+        Self { bins }
+    }
+
+    /// Return value at a wavelength in nanometers.
+    ///
+    /// Wavelengths outside the binned range take the value of the nearest bin.
+    pub fn value_at(&self, lambda: f32) -> f32 {
+        let position = (lambda - BIN_MIN) / (BIN_MAX - BIN_MIN) * self.bins.len() as f32;
+        let index = (position.max(0.0) as usize).min(self.bins.len() - 1);
+        self.bins[index]
+    }
+
+    /// Return values at the sampled wavelengths.
+    pub fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        SampledSpectrum(wavelengths.lambda.map(|lambda| self.value_at(lambda)))
+    }
+}
+
+/// Index of refraction, possibly depending on wavelength.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ior {
+    /// Same index at all wavelengths.
+    Constant(f32),
+    /// Cauchy's equation `n = a + b / λ²`, with λ in micrometers.
+    Cauchy {
+        /// Constant term
+        a: f32,
+        /// Coefficient in µm²
+        b: f32,
+    },
+    /// Sellmeier equation `n² = 1 + Σ bᵢ λ² / (λ² - cᵢ)`, with λ in micrometers.
+    Sellmeier {
+        /// Dimensionless coefficients
+        b: [f32; 3],
+        /// Coefficients in µm²
+        c: [f32; 3],
+    },
+}
+
+impl Ior {
+    /// Schott N-BK7 crown glass.
+    pub const BK7: Ior = Ior::Sellmeier {
+        b: [1.039_612, 0.231_792_34, 1.010_469_5],
+        c: [0.006_000_699, 0.020_017_914, 103.560_65],
+    };
+
+    /// Return the index of refraction at a wavelength in nanometers.
+    pub fn at(&self, lambda: f32) -> f32 {
+        let micrometers = lambda / 1000.0;
+        let squared = micrometers * micrometers;
+        match *self {
+            Ior::Constant(n) => n,
+            Ior::Cauchy { a, b } => a + b / squared,
+            Ior::Sellmeier { b, c } => {
+                let sum: f32 = (0..3).map(|i| b[i] * squared / (squared - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    /// Return whether the index changes with wavelength.
+    ///
+    /// Paths refracting through a dispersive interface have to call
+    /// [`SampledWavelengths::terminate_secondary`].
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+}
+
+#[cfg(test)]
+mod test_spectrum {
+    use crate::color::Color;
+    use crate::rng::Pcg32;
+
+    use super::*;
+
+    #[test]
+    fn hero_wavelengths_are_evenly_spaced() {
+        let wavelengths = SampledWavelengths::sample_uniform(0.9);
+        assert_relative_eq!(wavelengths.lambda[0], 783.0, epsilon = 1e-3);
+        assert_relative_eq!(wavelengths.lambda[1], 430.5, epsilon = 1e-3);
+        assert_relative_eq!(wavelengths.lambda[2], 548.0, epsilon = 1e-3);
+        assert_relative_eq!(wavelengths.lambda[3], 665.5, epsilon = 1e-3);
+        assert!(wavelengths.pdf.iter().all(|&pdf| pdf == 1.0 / 470.0));
+    }
+
+    #[test]
+    fn terminate_secondary_keeps_hero() {
+        let mut wavelengths = SampledWavelengths::sample_uniform(0.5);
+        assert!(!wavelengths.secondary_terminated());
+        wavelengths.terminate_secondary();
+        wavelengths.terminate_secondary();
+        assert!(wavelengths.secondary_terminated());
+        assert_relative_eq!(wavelengths.pdf[0], 1.0 / 1880.0);
+        // The estimate stays unbiased with only the hero wavelength.
+        let mut rng = Pcg32::new(7);
+        let mut luminance = 0.0;
+        for _ in 0..20000 {
+            let mut wavelengths = SampledWavelengths::sample_uniform(rng.next_f32());
+            wavelengths.terminate_secondary();
+            luminance += SampledSpectrum::constant(1.0).to_xyz(&wavelengths).y;
+        }
+        assert_relative_eq!(luminance / 20000.0, 1.0, epsilon = 2e-2);
+    }
+
+    #[test]
+    fn uplifted_colors_round_trip() {
+        let colors = [
+            Color::new(1.0, 1.0, 1.0),
+            Color::new(0.5, 0.5, 0.5),
+            Color::new(0.8, 0.3, 0.1),
+            Color::new(0.1, 0.6, 0.9),
+        ];
+        for color in colors.iter() {
+            let spectrum = RgbSpectrum::from_rgb(*color);
+            let mut rgb = Color::new(0.0, 0.0, 0.0);
+            let count = 400;
+            for i in 0..count {
+                let wavelengths =
+                    SampledWavelengths::sample_uniform((i as f32 + 0.5) / count as f32);
+                rgb = rgb + spectrum.sample(&wavelengths).to_rgb(&wavelengths);
+            }
+            let rgb = rgb * (1.0 / count as f32);
+            assert_relative_eq!(rgb, *color, epsilon = 0.08);
+        }
+    }
+
+    #[test]
+    fn uplifted_spectra_stay_physical() {
+        let spectrum = RgbSpectrum::from_rgb(Color::new(0.9, 0.2, 0.4));
+        for lambda in 360..=830 {
+            let value = spectrum.value_at(lambda as f32);
+            assert!((0.0..=1.02).contains(&value));
+        }
+        assert!(spectrum.value_at(700.0) > spectrum.value_at(530.0));
+    }
+
+    #[test]
+    fn spectrum_arithmetic() {
+        let a = SampledSpectrum([1.0, 2.0, 3.0, 4.0]);
+        let b = SampledSpectrum::constant(2.0);
+        assert!(a + b == SampledSpectrum([3.0, 4.0, 5.0, 6.0]));
+        assert!(a * b == a * 2.0);
+        assert!(a * b / 2.0 == a);
+    }
+
+    #[test]
+    fn glass_disperses() {
+        assert_relative_eq!(Ior::BK7.at(587.6), 1.5168, epsilon = 1e-4);
+        assert!(Ior::BK7.at(450.0) > Ior::BK7.at(650.0));
+        let cauchy = Ior::Cauchy {
+            a: 1.5046,
+            b: 0.00420,
+        };
+        assert_relative_eq!(cauchy.at(500.0), 1.5214, epsilon = 1e-4);
+        assert!(cauchy.is_dispersive());
+        assert!(!Ior::Constant(1.5).is_dispersive());
+        assert_relative_eq!(Ior::Constant(1.5).at(400.0), 1.5);
+    }
+}
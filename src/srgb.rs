@@ -0,0 +1,219 @@
+//! Conversions between linear and sRGB encoded colors.
+//!
+//! Rendering happens on linear values, but 8-bit images are expected to be
+//! sRGB encoded. Writing linear values straight to 8 bits makes renders look
+//! too dark, and reading sRGB textures as linear makes them too bright. The
+//! [`LinearRgb`] and [`Srgb`] wrappers make the encoding of a color explicit.
+
+use std::sync::OnceLock;
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+/// Color with linear channel values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearRgb(pub Color<f32>);
+
+/// Color with sRGB encoded channel values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Srgb(pub Color<f32>);
+
+impl From<LinearRgb> for Srgb {
+    fn from(color: LinearRgb) -> Self {
+        let LinearRgb(c) = color;
+        Srgb(Color::new(encode(c.r), encode(c.g), encode(c.b)))
+    }
+}
+
+impl From<Srgb> for LinearRgb {
+    fn from(color: Srgb) -> Self {
+        let Srgb(c) = color;
+        LinearRgb(Color::new(decode(c.r), decode(c.g), decode(c.b)))
+    }
+}
+
+impl LinearRgb {
+    /// Return sRGB encoded 8-bit color, using a lookup table.
+    pub fn as_srgb8(self) -> Color<u8> {
+        let LinearRgb(c) = self;
+        Color::new(encode_u8(c.r), encode_u8(c.g), encode_u8(c.b))
+    }
+}
+
+impl Srgb {
+    /// Return linear color from sRGB encoded 8-bit color, using a lookup table.
+    pub fn linear_from_srgb8(color: Color<u8>) -> LinearRgb {
+        LinearRgb(Color::new(
+            decode_u8(color.r),
+            decode_u8(color.g),
+            decode_u8(color.b),
+        ))
+    }
+}
+
+/// Transfer function of an image format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Values are stored as they are.
+    Linear,
+    /// Values are stored with the sRGB transfer function.
+    Srgb,
+}
+
+impl Encoding {
+    /// Return the encoding images with the given file extension use.
+    ///
+    /// Floating point formats store linear values, 8-bit formats sRGB.
+    pub fn for_extension(extension: &str) -> Self {
+        match extension.to_ascii_lowercase().as_str() {
+            "hdr" | "exr" | "pfm" => Encoding::Linear,
+            _ => Encoding::Srgb,
+        }
+    }
+
+    /// Convert a linear color to an 8-bit color in this encoding.
+    pub fn to_rgb8(self, color: Color<f32>) -> Color<u8> {
+        match self {
+            Encoding::Linear => color.as_rgb8(),
+            Encoding::Srgb => LinearRgb(color).as_srgb8(),
+        }
+    }
+
+    /// Convert all pixels of a canvas in this encoding to linear values.
+    pub fn decode_canvas(self, canvas: &mut Canvas) {
+        if self == Encoding::Srgb {
+            for pixel in canvas.pixels.iter_mut() {
+                *pixel = LinearRgb::from(Srgb(*pixel)).0;
+            }
+        }
+    }
+}
+
+/// sRGB opto-electronic transfer function, from linear to encoded.
+pub fn encode(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// sRGB electro-optical transfer function, from encoded to linear.
+pub fn decode(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Decoded linear value of every 8-bit code.
+fn decode_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (code, value) in table.iter_mut().enumerate() {
+            *value = decode(code as f32 / 255.0);
+        }
+        table
+    })
+}
+
+/// Linear values halfway between consecutive 8-bit codes, in encoded space.
+fn encode_thresholds() -> &'static [f32; 255] {
+    static TABLE: OnceLock<[f32; 255]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 255];
+        for (code, value) in table.iter_mut().enumerate() {
+            *value = decode((code as f32 + 0.5) / 255.0);
+        }
+        table
+    })
+}
+
+/// Decode an 8-bit sRGB value to linear.
+pub fn decode_u8(code: u8) -> f32 {
+    decode_table()[code as usize]
+}
+
+/// Encode a linear value to 8-bit sRGB, rounding to the nearest code.
+pub fn encode_u8(value: f32) -> u8 {
+    encode_thresholds().partition_point(|&threshold| threshold <= value) as u8
+}
+
+#[cfg(test)]
+mod test_srgb {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+
+    use super::*;
+
+    #[test]
+    fn transfer_functions() {
+        assert_relative_eq!(encode(0.5), 0.735_357, epsilon = 1e-6);
+        assert_relative_eq!(decode(0.5), 0.214_041, epsilon = 1e-6);
+        assert_relative_eq!(encode(0.0), 0.0);
+        assert_relative_eq!(encode(1.0), 1.0, epsilon = 1e-6);
+        // Both pieces meet at the breakpoint.
+        assert_relative_eq!(encode(0.003_130_8), 0.040_45, epsilon = 1e-5);
+        for i in 0..=100 {
+            let value = i as f32 / 100.0;
+            assert_relative_eq!(decode(encode(value)), value, epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn lookup_tables_match_transfer_functions() {
+        for code in 0..=255u8 {
+            let linear = decode(code as f32 / 255.0);
+            assert_relative_eq!(decode_u8(code), linear);
+            assert_eq!(encode_u8(linear), code);
+        }
+        assert_eq!(encode_u8(-1.0), 0);
+        assert_eq!(encode_u8(2.0), 255);
+        assert_eq!(encode_u8(0.5), 188);
+    }
+
+    #[test]
+    fn convert_between_wrappers() {
+        let linear = LinearRgb(Color::new(0.5, 0.18, 1.0));
+        let encoded = Srgb::from(linear);
+        assert_relative_eq!(
+            encoded.0,
+            Color::new(0.735_357, 0.461_356, 1.0),
+            epsilon = 1e-5
+        );
+        assert_relative_eq!(LinearRgb::from(encoded).0, linear.0, epsilon = 1e-5);
+        assert!(linear.as_srgb8() == Color::new(188, 118, 255));
+        let decoded = Srgb::linear_from_srgb8(Color::new(188, 118, 255));
+        assert_relative_eq!(
+            decoded.0,
+            Color::new(0.502_886, 0.181_164, 1.0),
+            epsilon = 1e-5
+        );
+    }
+
+    #[test]
+    fn encoding_per_format() {
+        assert!(Encoding::for_extension("ppm") == Encoding::Srgb);
+        assert!(Encoding::for_extension("PNG") == Encoding::Srgb);
+        assert!(Encoding::for_extension("hdr") == Encoding::Linear);
+        let color = Color::new(0.5, 0.5, 0.5);
+        assert!(Encoding::Linear.to_rgb8(color) == Color::new(128, 128, 128));
+        assert!(Encoding::Srgb.to_rgb8(color) == Color::new(188, 188, 188));
+    }
+
+    #[test]
+    fn decode_canvas_to_linear() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.fill(Color::new(0.5, 0.5, 0.5));
+        Encoding::Linear.decode_canvas(&mut canvas);
+        assert_relative_eq!(canvas[(0, 0)], Color::new(0.5, 0.5, 0.5));
+        Encoding::Srgb.decode_canvas(&mut canvas);
+        assert_relative_eq!(
+            canvas[(0, 0)],
+            Color::new(0.214_041, 0.214_041, 0.214_041),
+            epsilon = 1e-6
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! Statistics of canvas values, for checking exposure and lighting.
+//!
+//! [`Statistics`] summarizes a channel with its range, mean, percentiles and
+//! count of NaN and infinite values, which usually point at bugs in shading.
+//! [`Histogram`] counts values in bins and can be drawn into a small canvas.
+//! [`auto_exposure`] picks an exposure for [`ToneMapping`] from the log
+//! average luminance, as in Reinhard et al. (2002).
+//!
+//! [`ToneMapping`]: crate::tonemap::ToneMapping
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+/// Value of a pixel to collect statistics on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// Red
+    Red,
+    /// Green
+    Green,
+    /// Blue
+    Blue,
+    /// Relative luminance
+    Luminance,
+}
+
+impl Channel {
+    /// Return value of this channel for a color.
+    pub fn value(self, color: Color<f32>) -> f32 {
+        match self {
+            Channel::Red => color.r,
+            Channel::Green => color.g,
+            Channel::Blue => color.b,
+            Channel::Luminance => color.luminance(),
+        }
+    }
+}
+
+/// Summary of the values of one channel.
+///
+/// Range, mean and percentiles only consider finite values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    /// Smallest finite value, NaN if there is none
+    pub minimum: f32,
+    /// Largest finite value, NaN if there is none
+    pub maximum: f32,
+    /// Mean of finite values, NaN if there is none
+    pub mean: f32,
+    /// Number of NaN values
+    pub nan_count: usize,
+    /// Number of positive or negative infinite values
+    pub infinite_count: usize,
+    sorted: Vec<f32>,
+}
+
+impl Statistics {
+    /// Collect statistics of a channel over all pixels.
+    pub fn new(canvas: &Canvas, channel: Channel) -> Self {
+        let values = canvas.pixels.iter().map(|&pixel| channel.value(pixel));
+        let mut sorted = vec![];
+        let (mut nan_count, mut infinite_count) = (0, 0);
+        for value in values {
+            if value.is_nan() {
+                nan_count += 1;
+            } else if value.is_infinite() {
+                infinite_count += 1;
+            } else {
+                sorted.push(value);
+            }
+        }
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mean = sorted.iter().map(|&value| value as f64).sum::<f64>() / sorted.len() as f64;
+        Self {
+            minimum: sorted.first().copied().unwrap_or(f32::NAN),
+            maximum: sorted.last().copied().unwrap_or(f32::NAN),
+            mean: mean as f32,
+            nan_count,
+            infinite_count,
+            sorted,
+        }
+    }
+
+    /// Return number of finite values.
+    pub fn finite_count(&self) -> usize {
+        self.sorted.len()
+    }
+
+    /// Return value below which the given percentage of finite values lie.
+    ///
+    /// Interpolates linearly between the closest values, and returns NaN if
+    /// there are no finite values.
+    pub fn percentile(&self, percent: f32) -> f32 {
+        if self.sorted.is_empty() {
+            return f32::NAN;
+        }
+        let position = (percent / 100.0).clamp(0.0, 1.0) * (self.sorted.len() - 1) as f32;
+        let below = position.floor() as usize;
+        let above = (below + 1).min(self.sorted.len() - 1);
+        let t = position - below as f32;
+        self.sorted[below] * (1.0 - t) + self.sorted[above] * t
+    }
+
+    /// Return median of finite values.
+    pub fn median(&self) -> f32 {
+        self.percentile(50.0)
+    }
+}
+
+/// Count of values in equally wide bins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Lower end of the first bin
+    pub minimum: f32,
+    /// Upper end of the last bin
+    pub maximum: f32,
+    /// Number of values in each bin
+    pub bins: Vec<usize>,
+    /// Number of values below the minimum
+    pub below: usize,
+    /// Number of values above the maximum
+    pub above: usize,
+}
+
+impl Histogram {
+    /// Count values of a channel in bins from `minimum` to `maximum`.
+    ///
+    /// NaN values are skipped, infinite ones count as below or above.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no bins or the range is empty.
+    pub fn new(canvas: &Canvas, channel: Channel, bins: usize, minimum: f32, maximum: f32) -> Self {
+        assert!(bins > 0 && minimum < maximum, "empty histogram");
+        let mut histogram = Self {
+            minimum,
+            maximum,
+            bins: vec![0; bins],
+            below: 0,
+            above: 0,
+        };
+        let scale = bins as f32 / (maximum - minimum);
+        for &pixel in canvas.pixels.iter() {
+            let value = channel.value(pixel);
+            if value < minimum {
+                histogram.below += 1;
+            } else if value > maximum {
+                histogram.above += 1;
+            } else if !value.is_nan() {
+                let bin = (((value - minimum) * scale) as usize).min(bins - 1);
+                histogram.bins[bin] += 1;
+            }
+        }
+        histogram
+    }
+
+    /// Return lower and upper end of a bin.
+    pub fn bin_range(&self, bin: usize) -> (f32, f32) {
+        let width = (self.maximum - self.minimum) / self.bins.len() as f32;
+        (
+            self.minimum + bin as f32 * width,
+            self.minimum + (bin + 1) as f32 * width,
+        )
+    }
+
+    /// Draw histogram as bars of a color, one column per bin.
+    ///
+    /// Bars are scaled so the fullest bin reaches the top.
+    pub fn to_canvas(&self, height: usize, color: Color<f32>) -> Canvas {
+        overlay(&[(self, color)], height)
+    }
+}
+
+/// Draw several histograms with the same number of bins on top of each other.
+///
+/// Colors of overlapping bars are added, so red, green and blue histograms
+/// show white where they agree. All bars share the same scale.
+///
+/// # Panics
+///
+/// Panics if the histograms differ in the number of bins.
+pub fn overlay(histograms: &[(&Histogram, Color<f32>)], height: usize) -> Canvas {
+    let width = histograms
+        .first()
+        .map_or(0, |(histogram, _)| histogram.bins.len());
+    assert!(
+        histograms
+            .iter()
+            .all(|(histogram, _)| histogram.bins.len() == width),
+        "histograms differ in bins"
+    );
+    let fullest = histograms
+        .iter()
+        .flat_map(|(histogram, _)| histogram.bins.iter())
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let mut canvas = Canvas::new(height, width);
+    for (histogram, color) in histograms {
+        for (column, &count) in histogram.bins.iter().enumerate() {
+            let bar = (count * height + fullest / 2) / fullest;
+            for row in height - bar..height {
+                canvas[(row, column)] = canvas[(row, column)] + *color;
+            }
+        }
+    }
+    canvas
+}
+
+/// Offset keeping black pixels from pulling the log average to zero.
+const DELTA: f64 = 1e-4;
+
+/// Return geometric mean of the luminance, ignoring non-finite values.
+pub fn log_average_luminance(canvas: &Canvas) -> f32 {
+    let (sum, count) = canvas
+        .pixels
+        .iter()
+        .map(|pixel| pixel.luminance())
+        .filter(|luminance| luminance.is_finite())
+        .fold((0.0, 0), |(sum, count), luminance| {
+            (sum + (DELTA + luminance.max(0.0) as f64).ln(), count + 1)
+        });
+    if count == 0 {
+        0.0
+    } else {
+        ((sum / count as f64).exp() - DELTA) as f32
+    }
+}
+
+/// Return exposure in stops that maps the log average luminance to `key`.
+///
+/// A key of 0.18 gives middle gray; brighter keys suit bright scenes. Use
+/// the result as [`ToneMapping::exposure`](crate::tonemap::ToneMapping::exposure).
+/// Black canvases get no adjustment.
+pub fn auto_exposure(canvas: &Canvas, key: f32) -> f32 {
+    let average = log_average_luminance(canvas);
+    if average as f64 > DELTA * 1e-3 {
+        (key / average).log2()
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod test_statistics {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::tonemap::ToneMapping;
+
+    use super::*;
+
+    fn gray(value: f32) -> Color<f32> {
+        Color::new(value, value, value)
+    }
+
+    fn ramp() -> Canvas {
+        let mut canvas = Canvas::new(2, 5);
+        for (index, pixel) in canvas.pixels.iter_mut().enumerate() {
+            *pixel = Color::new(index as f32, 1.0, 0.0);
+        }
+        canvas
+    }
+
+    #[test]
+    fn statistics_of_channel() {
+        let mut canvas = ramp();
+        canvas[(1, 4)] = Color::new(f32::NAN, 1.0, 0.0);
+        canvas[(1, 3)] = Color::new(f32::INFINITY, 1.0, 0.0);
+        let red = Statistics::new(&canvas, Channel::Red);
+        assert_eq!(
+            (red.nan_count, red.infinite_count, red.finite_count()),
+            (1, 1, 8)
+        );
+        assert_relative_eq!(red.minimum, 0.0);
+        assert_relative_eq!(red.maximum, 7.0);
+        assert_relative_eq!(red.mean, 3.5);
+        assert_relative_eq!(red.median(), 3.5);
+        assert_relative_eq!(red.percentile(0.0), 0.0);
+        assert_relative_eq!(red.percentile(100.0), 7.0);
+        assert_relative_eq!(red.percentile(10.0), 0.7);
+        let green = Statistics::new(&canvas, Channel::Green);
+        assert_eq!(green.nan_count, 0);
+        assert_relative_eq!(green.percentile(90.0), 1.0);
+        assert!(Statistics::new(&Canvas::new(0, 0), Channel::Blue)
+            .mean
+            .is_nan());
+    }
+
+    #[test]
+    fn histogram_bins() {
+        let mut canvas = ramp();
+        canvas[(0, 0)] = Color::new(-1.0, 0.0, 0.0);
+        canvas[(0, 1)] = Color::new(f32::NAN, 0.0, 0.0);
+        let histogram = Histogram::new(&canvas, Channel::Red, 4, 0.0, 8.0);
+        assert!(histogram.bins == vec![0, 2, 2, 3]);
+        assert_eq!((histogram.below, histogram.above), (1, 1));
+        assert_eq!(histogram.bin_range(1), (2.0, 4.0));
+        let luminance = Histogram::new(&canvas, Channel::Luminance, 2, 0.0, 1.0);
+        let counted: usize = luminance.bins.iter().sum();
+        assert_eq!(counted + luminance.below + luminance.above, 9);
+    }
+
+    #[test]
+    fn draw_histograms() {
+        let histogram = Histogram {
+            minimum: 0.0,
+            maximum: 1.0,
+            bins: vec![4, 2, 0],
+            below: 0,
+            above: 0,
+        };
+        let canvas = histogram.to_canvas(4, Color::new(1.0, 0.0, 0.0));
+        assert_eq!((canvas.height, canvas.width), (4, 3));
+        assert_relative_eq!(canvas[(0, 0)], Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(canvas[(1, 1)], gray(0.0));
+        assert_relative_eq!(canvas[(2, 1)], Color::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(canvas[(3, 2)], gray(0.0));
+
+        let other = Histogram {
+            bins: vec![4, 4, 4],
+            ..histogram.clone()
+        };
+        let canvas = overlay(
+            &[
+                (&histogram, Color::new(1.0, 0.0, 0.0)),
+                (&other, Color::new(0.0, 1.0, 1.0)),
+            ],
+            4,
+        );
+        assert_relative_eq!(canvas[(3, 0)], gray(1.0));
+        assert_relative_eq!(canvas[(0, 2)], Color::new(0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn exposure_from_key() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.fill(gray(0.72));
+        assert_relative_eq!(log_average_luminance(&canvas), 0.72, epsilon = 1e-5);
+        assert_relative_eq!(auto_exposure(&canvas, 0.18), -2.0, epsilon = 1e-4);
+
+        // Log average is the geometric mean, so a bright highlight barely
+        // moves it compared to the arithmetic mean.
+        canvas[(0, 0)] = gray(100.0);
+        canvas[(0, 1)] = gray(f32::NAN);
+        let average = log_average_luminance(&canvas);
+        assert!(average < 5.0);
+        let exposure = auto_exposure(&canvas, 0.18);
+        let mapped = ToneMapping {
+            exposure,
+            ..ToneMapping::default()
+        }
+        .apply(&canvas);
+        assert_relative_eq!(log_average_luminance(&mapped), 0.18, epsilon = 1e-3);
+        assert_relative_eq!(auto_exposure(&Canvas::new(1, 1), 0.18), 0.0);
+    }
+}
@@ -0,0 +1,361 @@
+//! Textures looked up by uv-coordinates.
+//!
+//! Textures return a color for a `(u, v)` pair, typically produced by one of
+//! the mappings in [`crate::uv`]. The v-axis points up, so `v = 0` is the
+//! bottom row of an image.
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::srgb::Encoding;
+
+/// How texels are combined when sampling an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Use the closest texel.
+    Nearest,
+    /// Linearly interpolate the four closest texels.
+    Bilinear,
+    /// Catmull-Rom interpolation of the sixteen closest texels.
+    Bicubic,
+}
+
+/// How lookups outside of the image are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Tile the image.
+    Repeat,
+    /// Use the closest edge texel.
+    Clamp,
+    /// Tile the image, flipping every other copy.
+    Mirror,
+}
+
+/// Texture sampling an image stored in a canvas.
+pub struct ImageTexture {
+    /// Source image
+    pub canvas: Canvas,
+    /// Filter used for lookups
+    pub filter: Filter,
+    /// Wrap mode used for lookups along u
+    pub wrap_u: WrapMode,
+    /// Wrap mode used for lookups along v
+    pub wrap_v: WrapMode,
+}
+
+impl ImageTexture {
+    /// Construct a new texture with bilinear filtering that repeats.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the canvas is empty.
+    pub fn new(canvas: Canvas) -> Self {
+        assert!(!canvas.pixels.is_empty(), "empty texture");
+        Self {
+            canvas,
+            filter: Filter::Bilinear,
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::Repeat,
+        }
+    }
+
+    /// Construct a new texture from an sRGB encoded image, such as a PPM file.
+    ///
+    /// Pixels are decoded to linear values, so that lookups can be used in
+    /// shading directly.
+    pub fn from_srgb(mut canvas: Canvas) -> Self {
+        Encoding::Srgb.decode_canvas(&mut canvas);
+        Self::new(canvas)
+    }
+
+    /// Return texture with the given filter.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Return texture with the given wrap mode along both axes.
+    pub fn with_wrap(self, wrap: WrapMode) -> Self {
+        self.with_wrap_uv(wrap, wrap)
+    }
+
+    /// Return texture with separate wrap modes along u and v.
+    pub fn with_wrap_uv(mut self, wrap_u: WrapMode, wrap_v: WrapMode) -> Self {
+        self.wrap_u = wrap_u;
+        self.wrap_v = wrap_v;
+        self
+    }
+
+    /// Sample the texture at uv-coordinates.
+    pub fn color_at(&self, u: f32, v: f32) -> Color<f32> {
+        // Continuous texel coordinates, with texel centers at integers.
+        let x = u * self.canvas.width as f32 - 0.5;
+        let y = (1.0 - v) * self.canvas.height as f32 - 0.5;
+        match self.filter {
+            Filter::Nearest => self.texel(x.round() as isize, y.round() as isize),
+            Filter::Bilinear => self.bilinear(x, y),
+            Filter::Bicubic => self.bicubic(x, y),
+        }
+    }
+
+    fn bilinear(&self, x: f32, y: f32) -> Color<f32> {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+        let top = lerp(self.texel(x0, y0), self.texel(x0 + 1, y0), tx);
+        let bottom = lerp(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), tx);
+        lerp(top, bottom, ty)
+    }
+
+    fn bicubic(&self, x: f32, y: f32) -> Color<f32> {
+        let (x0, y0) = (x.floor(), y.floor());
+        let wx = catmull_rom_weights(x - x0);
+        let wy = catmull_rom_weights(y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+        let mut result = Color::new(0.0, 0.0, 0.0);
+        for (j, weight_y) in wy.iter().enumerate() {
+            let row = y0 - 1 + j as isize;
+            for (i, weight_x) in wx.iter().enumerate() {
+                let column = x0 - 1 + i as isize;
+                result = result + self.texel(column, row) * (weight_x * weight_y);
+            }
+        }
+        result
+    }
+
+    /// Get texel at possibly out of bounds column and row, applying the wrap mode.
+    fn texel(&self, column: isize, row: isize) -> Color<f32> {
+        let column = wrap_index(column, self.canvas.width, self.wrap_u);
+        let row = wrap_index(row, self.canvas.height, self.wrap_v);
+        self.canvas[(row, column)]
+    }
+}
+
+/// Checkerboard in uv-space, useful for inspecting uv layouts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvCheckers {
+    /// Number of squares along u
+    pub width: usize,
+    /// Number of squares along v
+    pub height: usize,
+    /// Color of square at the origin
+    pub a: Color<f32>,
+    /// Color of the other squares
+    pub b: Color<f32>,
+}
+
+impl UvCheckers {
+    /// Convenience function for creating a new checker pattern.
+    pub fn new(width: usize, height: usize, a: Color<f32>, b: Color<f32>) -> Self {
+        Self {
+            width,
+            height,
+            a,
+            b,
+        }
+    }
+
+    /// Return color of the checker pattern at uv-coordinates.
+    pub fn color_at(&self, u: f32, v: f32) -> Color<f32> {
+        let u2 = (u * self.width as f32).floor() as i64;
+        let v2 = (v * self.height as f32).floor() as i64;
+        if (u2 + v2).rem_euclid(2) == 0 {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+/// Pattern marking the corners of uv-space, useful for checking orientation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvAlignCheck {
+    /// Color of the center
+    pub main: Color<f32>,
+    /// Color of the upper left corner
+    pub ul: Color<f32>,
+    /// Color of the upper right corner
+    pub ur: Color<f32>,
+    /// Color of the bottom left corner
+    pub bl: Color<f32>,
+    /// Color of the bottom right corner
+    pub br: Color<f32>,
+}
+
+impl UvAlignCheck {
+    /// Return color of the alignment pattern at uv-coordinates.
+    pub fn color_at(&self, u: f32, v: f32) -> Color<f32> {
+        if v > 0.8 {
+            if u < 0.2 {
+                return self.ul;
+            }
+            if u > 0.8 {
+                return self.ur;
+            }
+        } else if v < 0.2 {
+            if u < 0.2 {
+                return self.bl;
+            }
+            if u > 0.8 {
+                return self.br;
+            }
+        }
+        self.main
+    }
+}
+
+fn lerp(a: Color<f32>, b: Color<f32>, t: f32) -> Color<f32> {
+    a * (1.0 - t) + b * t
+}
+
+/// Catmull-Rom weights for the four texels around a fractional offset `t`.
+fn catmull_rom_weights(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
+/// Map an index into [0, size) according to the wrap mode.
+fn wrap_index(index: isize, size: usize, wrap: WrapMode) -> usize {
+    let size = size as isize;
+    let wrapped = match wrap {
+        WrapMode::Repeat => index.rem_euclid(size),
+        WrapMode::Clamp => index.clamp(0, size - 1),
+        WrapMode::Mirror => {
+            let period = index.rem_euclid(2 * size);
+            if period < size {
+                period
+            } else {
+                2 * size - 1 - period
+            }
+        }
+    };
+    wrapped as usize
+}
+
+#[cfg(test)]
+mod test_texture {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+
+    use super::*;
+
+    /// Two by two canvas with a distinct gray value per texel.
+    fn gradient_texture() -> ImageTexture {
+        let mut canvas = Canvas::new(2, 2);
+        canvas[(0, 0)] = Color::new(0.0, 0.0, 0.0);
+        canvas[(0, 1)] = Color::new(0.25, 0.25, 0.25);
+        canvas[(1, 0)] = Color::new(0.5, 0.5, 0.5);
+        canvas[(1, 1)] = Color::new(0.75, 0.75, 0.75);
+        ImageTexture::new(canvas)
+    }
+
+    #[test]
+    fn checker_pattern_in_2d() {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let checkers = UvCheckers::new(2, 2, black, white);
+        let cases = [
+            (0.0, 0.0, black),
+            (0.5, 0.0, white),
+            (0.0, 0.5, white),
+            (0.5, 0.5, black),
+            (1.0, 1.0, black),
+        ];
+        for (u, v, expected) in cases.iter() {
+            assert!(checkers.color_at(*u, *v) == *expected);
+        }
+    }
+
+    #[test]
+    fn align_check_pattern() {
+        let pattern = UvAlignCheck {
+            main: Color::new(1.0, 1.0, 1.0),
+            ul: Color::new(1.0, 0.0, 0.0),
+            ur: Color::new(1.0, 1.0, 0.0),
+            bl: Color::new(0.0, 1.0, 0.0),
+            br: Color::new(0.0, 1.0, 1.0),
+        };
+        assert!(pattern.color_at(0.5, 0.5) == pattern.main);
+        assert!(pattern.color_at(0.1, 0.9) == pattern.ul);
+        assert!(pattern.color_at(0.9, 0.9) == pattern.ur);
+        assert!(pattern.color_at(0.1, 0.1) == pattern.bl);
+        assert!(pattern.color_at(0.9, 0.1) == pattern.br);
+    }
+
+    #[test]
+    fn nearest_filter_picks_texel() {
+        let texture = gradient_texture().with_filter(Filter::Nearest);
+        // v = 1 is the top row of the canvas.
+        assert_relative_eq!(texture.color_at(0.1, 0.9), Color::new(0.0, 0.0, 0.0));
+        assert_relative_eq!(texture.color_at(0.9, 0.9), Color::new(0.25, 0.25, 0.25));
+        assert_relative_eq!(texture.color_at(0.1, 0.1), Color::new(0.5, 0.5, 0.5));
+        assert_relative_eq!(texture.color_at(0.9, 0.1), Color::new(0.75, 0.75, 0.75));
+    }
+
+    #[test]
+    fn bilinear_filter_interpolates() {
+        let texture = gradient_texture().with_wrap(WrapMode::Clamp);
+        // Exactly between all four texel centers.
+        assert_relative_eq!(texture.color_at(0.5, 0.5), Color::new(0.375, 0.375, 0.375));
+        // On a texel center.
+        assert_relative_eq!(texture.color_at(0.25, 0.75), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn bicubic_filter_reproduces_texel_centers() {
+        let texture = gradient_texture()
+            .with_filter(Filter::Bicubic)
+            .with_wrap(WrapMode::Clamp);
+        assert_relative_eq!(
+            texture.color_at(0.75, 0.25),
+            Color::new(0.75, 0.75, 0.75),
+            epsilon = 1e-6
+        );
+        let weights = catmull_rom_weights(0.3);
+        assert_relative_eq!(weights.iter().sum::<f32>(), 1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn srgb_texture_is_decoded() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.fill(Color::new(0.5, 1.0, 0.0));
+        let texture = ImageTexture::from_srgb(canvas);
+        assert_relative_eq!(
+            texture.color_at(0.5, 0.5),
+            Color::new(0.214_041, 1.0, 0.0),
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "empty texture")]
+    fn empty_canvas_panics() {
+        ImageTexture::new(Canvas::new(0, 0));
+    }
+
+    #[test]
+    fn wrap_modes_per_axis() {
+        // Repeats across the left edge but not across the top edge.
+        let texture = gradient_texture().with_wrap_uv(WrapMode::Repeat, WrapMode::Clamp);
+        assert_relative_eq!(texture.color_at(0.0, 1.0), Color::new(0.125, 0.125, 0.125));
+        let texture = gradient_texture().with_wrap_uv(WrapMode::Clamp, WrapMode::Repeat);
+        assert_relative_eq!(texture.color_at(0.0, 1.0), Color::new(0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    fn wrap_modes() {
+        assert_eq!(wrap_index(-1, 4, WrapMode::Repeat), 3);
+        assert_eq!(wrap_index(5, 4, WrapMode::Repeat), 1);
+        assert_eq!(wrap_index(-1, 4, WrapMode::Clamp), 0);
+        assert_eq!(wrap_index(5, 4, WrapMode::Clamp), 3);
+        assert_eq!(wrap_index(-1, 4, WrapMode::Mirror), 0);
+        assert_eq!(wrap_index(4, 4, WrapMode::Mirror), 3);
+        assert_eq!(wrap_index(5, 4, WrapMode::Mirror), 2);
+        assert_eq!(wrap_index(8, 4, WrapMode::Mirror), 0);
+    }
+}
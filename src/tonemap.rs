@@ -0,0 +1,278 @@
+//! Tone mapping of high dynamic range colors to displayable [0, 1] values.
+//!
+//! Rendered colors can be much brighter than 1.0, and simply clamping them
+//! blows out highlights. A [`ToneMapping`] scales colors by an exposure and
+//! compresses them with one of the operators in [`Operator`], before the
+//! conversion to 8-bit.
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::colorspace::multiply;
+
+/// Smallest white point of the operators that take one.
+pub const MIN_WHITE: f32 = 1e-3;
+
+/// Largest white point of the operators that take one.
+pub const MAX_WHITE: f32 = 1e4;
+
+/// Curve that compresses linear values to [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    /// No compression, values above 1.0 are clipped by the 8-bit conversion.
+    Clamp,
+    /// Reinhard's `x / (1 + x)`.
+    Reinhard,
+    /// Reinhard with a white point that maps to exactly 1.0.
+    ReinhardExtended {
+        /// Smallest value that becomes white, between [`MIN_WHITE`] and
+        /// [`MAX_WHITE`]
+        white: f32,
+    },
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable {
+        /// Linear white point, between [`MIN_WHITE`] and [`MAX_WHITE`]
+        white: f32,
+    },
+    /// Approximation of the AgX base look, which desaturates bright colors.
+    AgX,
+}
+
+/// Exposure adjustment followed by a tone mapping operator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapping {
+    /// Exposure in stops, colors are multiplied by `2^exposure`
+    pub exposure: f32,
+    /// Operator applied after exposure
+    pub operator: Operator,
+}
+
+impl Default for ToneMapping {
+    /// No exposure adjustment and clamping, which leaves colors untouched.
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            operator: Operator::Clamp,
+        }
+    }
+}
+
+impl ToneMapping {
+    /// Convenience function for creating a new tone mapping.
+    pub fn new(exposure: f32, operator: Operator) -> Self {
+        Self { exposure, operator }
+    }
+
+    /// Tone map a single color.
+    pub fn map_color(&self, color: Color<f32>) -> Color<f32> {
+        let color = color * 2.0_f32.powf(self.exposure);
+        match self.operator {
+            Operator::Clamp => color,
+            Operator::Reinhard => map_channels(color, |x| x / (1.0 + x)),
+            Operator::ReinhardExtended { white } => {
+                let white = white_point(white);
+                map_channels(color, |x| x * (1.0 + x / (white * white)) / (1.0 + x))
+            }
+            Operator::Aces => map_channels(color, aces),
+            Operator::Hable { white } => {
+                let scale = 1.0 / hable(white_point(white));
+                map_channels(color, |x| (hable(x) * scale).min(1.0))
+            }
+            Operator::AgX => agx(color),
+        }
+    }
+
+    /// Return a tone mapped copy of the canvas.
+    pub fn apply(&self, canvas: &Canvas) -> Canvas {
+        let mut result = Canvas::new(canvas.height, canvas.width);
+        for (target, source) in result.pixels.iter_mut().zip(canvas.pixels.iter()) {
+            *target = self.map_color(*source);
+        }
+        result
+    }
+}
+
+/// Limit a white point, so that zero, negative or NaN ones do not divide by
+/// zero.
+fn white_point(white: f32) -> f32 {
+    if white.is_nan() {
+        MIN_WHITE
+    } else {
+        white.clamp(MIN_WHITE, MAX_WHITE)
+    }
+}
+
+fn map_channels<F: Fn(f32) -> f32>(color: Color<f32>, function: F) -> Color<f32> {
+    Color::new(
+        function(color.r.max(0.0)),
+        function(color.g.max(0.0)),
+        function(color.b.max(0.0)),
+    )
+}
+
+fn aces(x: f32) -> f32 {
+    // The fit expects values pre-exposed by 0.6 to match the reference curve.
+    let x = x * 0.6;
+    let result = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    result.clamp(0.0, 1.0)
+}
+
+fn hable(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+/// Minimal AgX, after Benjamin Wrensch's fit of Troy Sobotka's AgX.
+fn agx(color: Color<f32>) -> Color<f32> {
+    const MIN_EV: f32 = -12.473_93;
+    const MAX_EV: f32 = 4.026_069;
+    // Inset into a slightly desaturated working space.
+    let inset = multiply(
+        &[
+            [0.842_479_1, 0.078_433_6, 0.079_223_75],
+            [0.042_328_24, 0.878_468_6, 0.079_166_13],
+            [0.042_375_66, 0.078_433_6, 0.879_143],
+        ],
+        color,
+    );
+    let encode = |x: f32| {
+        let log = x.max(1e-10).log2().clamp(MIN_EV, MAX_EV);
+        agx_contrast((log - MIN_EV) / (MAX_EV - MIN_EV))
+    };
+    let encoded = Color::new(encode(inset.r), encode(inset.g), encode(inset.b));
+    let outset = multiply(
+        &[
+            [1.196_879, -0.098_020_88, -0.099_029_74],
+            [-0.052_896_85, 1.151_903_1, -0.098_961_18],
+            [-0.052_971_64, -0.098_043_45, 1.151_073_7],
+        ],
+        encoded,
+    );
+    // The curve produces display encoded values, return to linear.
+    map_channels(outset, |x| x.powf(2.2).min(1.0))
+}
+
+/// Polynomial approximation of the AgX sigmoid on log encoded values.
+fn agx_contrast(x: f32) -> f32 {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+        - 0.00232
+}
+
+#[cfg(test)]
+mod test_tonemap {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+
+    use super::*;
+
+    fn gray(value: f32) -> Color<f32> {
+        Color::new(value, value, value)
+    }
+
+    #[test]
+    fn default_leaves_colors_untouched() {
+        let color = Color::new(2.0, 0.5, -0.1);
+        assert_relative_eq!(ToneMapping::default().map_color(color), color);
+    }
+
+    #[test]
+    fn exposure_scales_by_stops() {
+        let tone_mapping = ToneMapping::new(2.0, Operator::Clamp);
+        assert_relative_eq!(tone_mapping.map_color(gray(0.1)), gray(0.4));
+        let tone_mapping = ToneMapping::new(-1.0, Operator::Clamp);
+        assert_relative_eq!(tone_mapping.map_color(gray(0.1)), gray(0.05));
+    }
+
+    #[test]
+    fn reinhard_operators() {
+        let simple = ToneMapping::new(0.0, Operator::Reinhard);
+        assert_relative_eq!(simple.map_color(gray(1.0)), gray(0.5));
+        assert_relative_eq!(simple.map_color(gray(3.0)), gray(0.75));
+        let extended = ToneMapping::new(0.0, Operator::ReinhardExtended { white: 4.0 });
+        assert_relative_eq!(extended.map_color(gray(4.0)), gray(1.0));
+        assert!(extended.map_color(gray(1.0)).r > simple.map_color(gray(1.0)).r);
+    }
+
+    #[test]
+    fn filmic_operators_compress_highlights() {
+        let operators = [
+            Operator::Aces,
+            Operator::Hable { white: 11.2 },
+            Operator::AgX,
+        ];
+        for operator in operators.iter() {
+            let tone_mapping = ToneMapping::new(0.0, *operator);
+            let mut previous = -1.0;
+            for i in 0..100 {
+                let value = tone_mapping.map_color(gray(i as f32 * 0.5)).r;
+                assert!(value >= previous, "{:?} is not monotonic", operator);
+                assert!((0.0..=1.0).contains(&value));
+                previous = value;
+            }
+            assert!(tone_mapping.map_color(gray(0.0)).r < 0.01);
+            assert!(tone_mapping.map_color(gray(40.0)).r > 0.9);
+        }
+    }
+
+    #[test]
+    fn hable_maps_white_point_to_one() {
+        let tone_mapping = ToneMapping::new(0.0, Operator::Hable { white: 11.2 });
+        assert_relative_eq!(tone_mapping.map_color(gray(11.2)), gray(1.0));
+    }
+
+    #[test]
+    fn invalid_white_points_are_limited() {
+        let color = Color::new(0.5, 2.0, 100.0);
+        for white in [0.0, -1.0, f32::NAN, f32::INFINITY].iter() {
+            for operator in [
+                Operator::ReinhardExtended { white: *white },
+                Operator::Hable { white: *white },
+            ]
+            .iter()
+            {
+                let mapped = ToneMapping::new(0.0, *operator).map_color(color);
+                assert!(mapped.is_finite(), "{:?} gave {:?}", operator, mapped);
+            }
+        }
+        let zero = ToneMapping::new(0.0, Operator::Hable { white: 0.0 });
+        let smallest = ToneMapping::new(0.0, Operator::Hable { white: MIN_WHITE });
+        assert!(zero.map_color(color) == smallest.map_color(color));
+    }
+
+    #[test]
+    fn aces_reference_values() {
+        let tone_mapping = ToneMapping::new(0.0, Operator::Aces);
+        assert_relative_eq!(
+            tone_mapping.map_color(gray(0.18)).r,
+            0.14012,
+            epsilon = 1e-4
+        );
+        assert_relative_eq!(tone_mapping.map_color(gray(1.0)).r, 0.67329, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn agx_desaturates_bright_colors() {
+        let tone_mapping = ToneMapping::new(0.0, Operator::AgX);
+        let result = tone_mapping.map_color(Color::new(50.0, 0.0, 0.0));
+        assert!(result.g > 0.05 && result.b > 0.05);
+    }
+
+    #[test]
+    fn apply_to_canvas() {
+        let mut canvas = Canvas::new(2, 3);
+        canvas.fill(gray(1.0));
+        canvas[(1, 2)] = gray(3.0);
+        let result = ToneMapping::new(0.0, Operator::Reinhard).apply(&canvas);
+        assert!(result.height == 2 && result.width == 3);
+        assert_relative_eq!(result[(0, 0)], gray(0.5));
+        assert_relative_eq!(result[(1, 2)], gray(0.75));
+    }
+}
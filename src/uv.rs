@@ -0,0 +1,202 @@
+//! UV mappings from points on a surface to two dimensional texture space.
+//!
+//! All mappings expect a point in object space and return `(u, v)` with both
+//! coordinates in the range [0, 1).
+
+use num::traits::{Float, FloatConst};
+
+use crate::vectors::Vec3;
+
+/// Faces of a unit cube, used by the cube mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    /// Face at x = -1
+    Left,
+    /// Face at x = 1
+    Right,
+    /// Face at z = 1
+    Front,
+    /// Face at z = -1
+    Back,
+    /// Face at y = 1
+    Up,
+    /// Face at y = -1
+    Down,
+}
+
+/// Map a point on a sphere around the origin to uv-coordinates.
+pub fn spherical_map<T: Float + FloatConst>(point: &Vec3<T>) -> (T, T) {
+    let one = T::one();
+    let half = one / (one + one);
+    let theta = point.x.atan2(point.z);
+    let phi = (point.y / point.magnitude()).acos();
+    let raw_u = theta / T::TAU();
+    (one - (raw_u + half), one - phi / T::PI())
+}
+
+/// Map a point on the xz-plane to uv-coordinates, repeating every unit.
+pub fn planar_map<T: Float>(point: &Vec3<T>) -> (T, T) {
+    (rem_one(point.x), rem_one(point.z))
+}
+
+/// Map a point on a cylinder around the y-axis to uv-coordinates.
+///
+/// The u-coordinate wraps around the cylinder, v repeats every unit along y.
+pub fn cylindrical_map<T: Float + FloatConst>(point: &Vec3<T>) -> (T, T) {
+    let one = T::one();
+    let half = one / (one + one);
+    let theta = point.x.atan2(point.z);
+    let raw_u = theta / T::TAU();
+    (one - (raw_u + half), rem_one(point.y))
+}
+
+/// Return the face of the unit cube that the point lies on.
+pub fn cube_face<T: Float>(point: &Vec3<T>) -> CubeFace {
+    let abs_x = point.x.abs();
+    let abs_y = point.y.abs();
+    let abs_z = point.z.abs();
+    let coord = abs_x.max(abs_y).max(abs_z);
+
+    if coord == point.x {
+        CubeFace::Right
+    } else if coord == -point.x {
+        CubeFace::Left
+    } else if coord == point.y {
+        CubeFace::Up
+    } else if coord == -point.y {
+        CubeFace::Down
+    } else if coord == point.z {
+        CubeFace::Front
+    } else {
+        CubeFace::Back
+    }
+}
+
+/// Map a point on the unit cube to its face and the uv-coordinates on that face.
+pub fn cube_map<T: Float>(point: &Vec3<T>) -> (CubeFace, T, T) {
+    let one = T::one();
+    let two = one + one;
+    let face = cube_face(point);
+    let (u, v) = match face {
+        CubeFace::Front => (point.x + one, point.y + one),
+        CubeFace::Back => (one - point.x, point.y + one),
+        CubeFace::Left => (point.z + one, point.y + one),
+        CubeFace::Right => (one - point.z, point.y + one),
+        CubeFace::Up => (point.x + one, one - point.z),
+        CubeFace::Down => (point.x + one, point.z + one),
+    };
+    (face, rem_one(u / two), rem_one(v / two))
+}
+
+/// Wrap value into [0, 1).
+fn rem_one<T: Float>(value: T) -> T {
+    let wrapped = value - value.floor();
+    // Tiny negative values can round up to exactly one.
+    if wrapped >= T::one() {
+        T::zero()
+    } else {
+        wrapped
+    }
+}
+
+#[cfg(test)]
+mod test_uv {
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> Vec3<f64> {
+        Vec3 { x, y, z }
+    }
+
+    #[test]
+    fn spherical_mapping_on_3d_point() {
+        let half_sqrt = 2.0_f64.sqrt() / 2.0;
+        let cases = [
+            (point(0.0, 0.0, -1.0), (0.0, 0.5)),
+            (point(1.0, 0.0, 0.0), (0.25, 0.5)),
+            (point(0.0, 0.0, 1.0), (0.5, 0.5)),
+            (point(-1.0, 0.0, 0.0), (0.75, 0.5)),
+            (point(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (point(0.0, -1.0, 0.0), (0.5, 0.0)),
+            (point(half_sqrt, half_sqrt, 0.0), (0.25, 0.75)),
+        ];
+        for (p, (u, v)) in cases.iter() {
+            let result = spherical_map(p);
+            assert_relative_eq!(result.0, u);
+            assert_relative_eq!(result.1, v);
+        }
+    }
+
+    #[test]
+    fn planar_mapping_on_3d_point() {
+        let cases = [
+            (point(0.25, 0.0, 0.5), (0.25, 0.5)),
+            (point(0.25, 0.0, -0.25), (0.25, 0.75)),
+            (point(0.25, 0.5, -0.25), (0.25, 0.75)),
+            (point(1.25, 0.0, 0.5), (0.25, 0.5)),
+            (point(0.25, 0.0, -1.75), (0.25, 0.25)),
+            (point(1.0, 0.0, -1.0), (0.0, 0.0)),
+            (point(0.0, 0.0, 0.0), (0.0, 0.0)),
+        ];
+        for (p, (u, v)) in cases.iter() {
+            let result = planar_map(p);
+            assert_relative_eq!(result.0, u);
+            assert_relative_eq!(result.1, v);
+        }
+    }
+
+    #[test]
+    fn cylindrical_mapping_on_3d_point() {
+        let half_sqrt = 2.0_f64.sqrt() / 2.0;
+        let cases = [
+            (point(0.0, 0.0, -1.0), (0.0, 0.0)),
+            (point(0.0, 0.5, -1.0), (0.0, 0.5)),
+            (point(0.0, 1.0, -1.0), (0.0, 0.0)),
+            (point(half_sqrt, 0.5, -half_sqrt), (0.125, 0.5)),
+            (point(1.0, 0.5, 0.0), (0.25, 0.5)),
+            (point(half_sqrt, 0.5, half_sqrt), (0.375, 0.5)),
+            (point(0.0, -0.25, 1.0), (0.5, 0.75)),
+            (point(-half_sqrt, 0.5, half_sqrt), (0.625, 0.5)),
+            (point(-1.0, 1.25, 0.0), (0.75, 0.25)),
+            (point(-half_sqrt, 0.5, -half_sqrt), (0.875, 0.5)),
+        ];
+        for (p, (u, v)) in cases.iter() {
+            let result = cylindrical_map(p);
+            assert_relative_eq!(result.0, u);
+            assert_relative_eq!(result.1, v);
+        }
+    }
+
+    #[test]
+    fn identify_face_of_cube() {
+        let cases = [
+            (point(-1.0, 0.5, -0.25), CubeFace::Left),
+            (point(1.1, -0.75, 0.8), CubeFace::Right),
+            (point(0.1, 0.6, 0.9), CubeFace::Front),
+            (point(-0.7, 0.0, -2.0), CubeFace::Back),
+            (point(0.5, 1.0, 0.9), CubeFace::Up),
+            (point(-0.2, -1.3, 1.1), CubeFace::Down),
+        ];
+        for (p, face) in cases.iter() {
+            assert_eq!(cube_face(p), *face);
+        }
+    }
+
+    #[test]
+    fn cube_mapping_on_faces() {
+        let cases = [
+            (point(-0.5, 0.5, 1.0), CubeFace::Front, (0.25, 0.75)),
+            (point(0.5, -0.5, 1.0), CubeFace::Front, (0.75, 0.25)),
+            (point(0.5, 0.5, -1.0), CubeFace::Back, (0.25, 0.75)),
+            (point(-1.0, 0.5, -0.5), CubeFace::Left, (0.25, 0.75)),
+            (point(1.0, 0.5, 0.5), CubeFace::Right, (0.25, 0.75)),
+            (point(-0.5, 1.0, -0.5), CubeFace::Up, (0.25, 0.75)),
+            (point(-0.5, -1.0, 0.5), CubeFace::Down, (0.25, 0.75)),
+        ];
+        for (p, face, (u, v)) in cases.iter() {
+            let result = cube_map(p);
+            assert_eq!(result.0, *face);
+            assert_relative_eq!(result.1, u);
+            assert_relative_eq!(result.2, v);
+        }
+    }
+}
@@ -6,7 +6,7 @@
 //!
 //! # Examples
 //! ```
-//! use crate::vectors::{Vec3}
+//! use rust_raytracer::vectors::Vec3;
 //!
 //! /// Creates a three dimensional vector with 64-bit floats.
 //! let vec1 = Vec3::<f64>{x:1.0, y:2.0, z:3.0};
@@ -21,7 +21,7 @@ use approx::{AbsDiffEq, RelativeEq, UlpsEq};
 /// Vec3 is a three dimensional vector.
 ///
 /// The three dimensions are named x, y and z, respectively.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec3<T: Num> {
     /// x-dimension
     pub x: T,
@@ -34,7 +34,7 @@ pub struct Vec3<T: Num> {
 impl<T: Float + Num> Vec3<T> {
     /// Return vector magnitude.
     pub fn magnitude(&self) -> T {
-        return self.norm().sqrt();
+        self.norm().sqrt()
     }
 
     /// Return vector L1-norm.
@@ -52,6 +52,26 @@ impl<T: Float + Num> Vec3<T> {
         }
     }
 
+    /// Return normalized vector, or `None` if it has zero or non-finite length.
+    ///
+    /// Use this where degenerate vectors can occur, since [`normalize`]
+    /// turns them into NaN.
+    ///
+    /// [`normalize`]: Vec3::normalize
+    pub fn try_normalize(&self) -> Option<Self> {
+        let mag = self.magnitude();
+        if mag > T::zero() && mag.is_finite() {
+            Some(self.normalize())
+        } else {
+            None
+        }
+    }
+
+    /// Return whether all components are neither NaN nor infinite.
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+
     /// Compute dot product.
     pub fn dot(&self, rhs: Self) -> T {
         self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
@@ -67,6 +87,60 @@ impl<T: Float + Num> Vec3<T> {
     }
 }
 
+/// Orthonormal basis, for moving directions to and from a local frame.
+///
+/// Sampling routines work in a frame where `n` is the z-axis, such as the
+/// surface normal for BRDF sampling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onb<T: Num> {
+    /// First tangent, the local x-axis
+    pub s: Vec3<T>,
+    /// Second tangent, the local y-axis
+    pub t: Vec3<T>,
+    /// Normal, the local z-axis
+    pub n: Vec3<T>,
+}
+
+impl<T: Float + Num> Onb<T> {
+    /// Build a right-handed basis around a unit normal.
+    ///
+    /// Uses the branchless construction of Duff et al. (2017), which
+    /// improves on Frisvad's method near `n = (0, 0, -1)`.
+    pub fn from_normal(n: Vec3<T>) -> Self {
+        let one = T::one();
+        let sign = one.copysign(n.z);
+        let a = -one / (sign + n.z);
+        let b = n.x * n.y * a;
+        Self {
+            s: Vec3 {
+                x: one + sign * n.x * n.x * a,
+                y: sign * b,
+                z: -sign * n.x,
+            },
+            t: Vec3 {
+                x: b,
+                y: sign + n.y * n.y * a,
+                z: -n.y,
+            },
+            n,
+        }
+    }
+
+    /// Express a world space vector in the local frame.
+    pub fn to_local(&self, v: Vec3<T>) -> Vec3<T> {
+        Vec3 {
+            x: v.dot(self.s),
+            y: v.dot(self.t),
+            z: v.dot(self.n),
+        }
+    }
+
+    /// Express a local vector in world space.
+    pub fn to_world(&self, v: Vec3<T>) -> Vec3<T> {
+        self.s * v.x + self.t * v.y + self.n * v.z
+    }
+}
+
 impl<T: fmt::Display + Num + fmt::Debug> fmt::Display for Vec3<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "({:?}, {:?}, {:?})", self.x, self.y, self.z)
@@ -430,6 +504,35 @@ mod tests {
         assert_relative_eq!(vec1.normalize(), expected_result);
     }
 
+    #[test]
+    fn try_normalize_degenerate_vector() {
+        let vec1 = Vec3::<f32> {
+            x: 0.0,
+            y: 3.0,
+            z: 4.0,
+        };
+        let expected_result = Vec3::<f32> {
+            x: 0.0,
+            y: 0.6,
+            z: 0.8,
+        };
+        assert_relative_eq!(vec1.try_normalize().unwrap(), expected_result);
+
+        let zero = Vec3::<f32> {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        assert!(!zero.normalize().is_finite());
+        assert!(zero.try_normalize().is_none());
+        let nan = Vec3::<f32> {
+            x: f32::NAN,
+            y: 1.0,
+            z: 0.0,
+        };
+        assert!(nan.try_normalize().is_none());
+    }
+
     #[test]
     fn dot_product() {
         let vec1 = Vec3::<f64> {
@@ -470,4 +573,47 @@ mod tests {
         };
         assert_relative_eq!(vec2.cross(&vec1), expected_result2);
     }
+
+    #[test]
+    fn orthonormal_basis() {
+        let normals = [
+            Vec3::<f64> {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            Vec3::<f64> {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            Vec3::<f64> {
+                x: 1.0,
+                y: -2.0,
+                z: 0.5,
+            }
+            .normalize(),
+            Vec3::<f64> {
+                x: 1e-9,
+                y: 0.0,
+                z: -1.0,
+            }
+            .normalize(),
+        ];
+        let v = Vec3::<f64> {
+            x: 0.3,
+            y: -0.7,
+            z: 2.0,
+        };
+        for &n in normals.iter() {
+            let onb = Onb::from_normal(n);
+            assert_relative_eq!(onb.s.magnitude(), 1.0, epsilon = 1e-12);
+            assert_relative_eq!(onb.t.magnitude(), 1.0, epsilon = 1e-12);
+            assert_relative_eq!(onb.s.dot(onb.t), 0.0, epsilon = 1e-12);
+            assert_relative_eq!(onb.s.dot(n), 0.0, epsilon = 1e-12);
+            assert_relative_eq!(onb.s.cross(&onb.t), n, epsilon = 1e-12);
+            assert_relative_eq!(onb.to_local(n).z, 1.0, epsilon = 1e-12);
+            assert_relative_eq!(onb.to_world(onb.to_local(v)), v, epsilon = 1e-12);
+        }
+    }
 }